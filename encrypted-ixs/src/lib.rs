@@ -4,24 +4,1306 @@ use arcis_imports::*;
 mod circuits {
     use arcis_imports::*;
 
+    const BATCH_SIZE: usize = 8;
+    const BOOK_CAPACITY: usize = 16;
+    const RFQ_QUOTES: usize = 4;
+    // Pair statistics are revealed as powers of ten, and volumes only once an
+    // epoch has this many orders.
+    const MAGNITUDE_BANDS: usize = 20;
+    const MIN_STATS_COUNT: u64 = 5;
+    const POLICY_JURISDICTIONS: usize = 8;
+    // Clearing prices are quote-per-base in fixed point.
+    const PRICE_SCALE: u128 = 1_000_000_000;
+
+    // Status codes carried in a FillReceipt.
+    const STATUS_ACCEPTED: u8 = 0;
+    const STATUS_ZERO_AMOUNT: u8 = 1;
+    const STATUS_ZERO_MIN_OUT: u8 = 2;
+    const STATUS_SAME_TOKEN: u8 = 3;
+    const STATUS_BINDING_MISMATCH: u8 = 4;
+    const STATUS_EXPIRED: u8 = 5;
+    const STATUS_ZERO_DISPLAY: u8 = 6;
+
+    // Must match OrderType in the dex program.
+    const ORDER_LIMIT: u8 = 1;
+    const ORDER_STOP_LOSS: u8 = 2;
+    const ORDER_TAKE_PROFIT: u8 = 3;
+
     pub struct SwapOrder {
         amount_in: u64,
         amount_out_min: u64,
-        token_in: u64,  // Using u64 ID for PoC (Pubkey split or mapping)
-        token_out: u64, // Using u64 ID for PoC
+        token_in: [u128; 2],  // Mint pubkey as two little-endian limbs
+        token_out: [u128; 2],
+        nonce: u64,
+        // Trigger thresholds as oracle prices in the feed's own units. Only
+        // the one matching the TempWallet's order type is used; the rest are
+        // zero.
+        limit_price: u64,
+        stop_price: u64,
+        take_profit_price: u64,
+        // Iceberg clip size. Zero for orders that show their full size.
+        display_size: u64,
+        // Last slot at which the order may still be matched. Zero for orders
+        // that never expire.
+        expires_at_slot: u64,
+    }
+
+    fn same_mint(a: [u128; 2], b: [u128; 2]) -> bool {
+        a[0] == b[0] && a[1] == b[1]
+    }
+
+    // What the chain learns about a matched order. Every field except the
+    // nonce (already public through the settlement PDA seeds) is zeroed when
+    // the order is rejected, so invalid orders never leak their parameters.
+    // amount_out_min is never revealed; fills are checked by `verify_fill`.
+    pub struct MatchResult {
+        bound: bool,
+        valid: bool,
+        amount_in: u64,
+        token_in: [u128; 2],
+        token_out: [u128; 2],
         nonce: u64,
+        revision: u8,
+        deadline_slot: u64,
+        expired: bool,
     }
 
+    // Hidden side of an iceberg order: what is left in total and the clip size
+    // to replenish with after each fill.
+    pub struct IcebergState {
+        amount_in: u64,
+        amount_out_min: u64,
+        display_size: u64,
+    }
+
+    // The submitter's private view of what the MPC decided, including why an
+    // order was rejected.
+    pub struct FillReceipt {
+        status: u8,
+        amount_in: u64,
+        amount_out_min: u64,
+    }
+
+    // The plaintext arguments are the TempWallet's public view of the order.
+    // An order is only matched if its ciphertexts describe that same order.
+    // Iceberg orders are the exception for size: their deposit is only a cap,
+    // so the real total can sit anywhere below it, and the result reveals
+    // just the first clip. `revision` is echoed back untouched so the callback
+    // can drop results for ciphertexts that amend_order has since replaced.
+    // `deadline_slot` is the last slot at which the callback will still accept
+    // this result, and is echoed back for it to check. An order whose hidden
+    // expiry falls before it is rejected as expired, so a job that sat in the
+    // mempool can never settle it late.
+    // The whole order is also re-encrypted to `auditor` for the disclosure
+    // account; the program passes the submitter's own key when no auditor is
+    // registered.
     #[instruction]
-    pub fn match_order(order: Enc<Shared, SwapOrder>) -> SwapOrder {
+    pub fn match_order(
+        order: Enc<Shared, SwapOrder>,
+        public_nonce: u64,
+        public_amount_in: u64,
+        public_token_in_lo: u128,
+        public_token_in_hi: u128,
+        public_token_out_lo: u128,
+        public_token_out_hi: u128,
+        is_iceberg: bool,
+        revision: u8,
+        deadline_slot: u64,
+        auditor: Shared,
+        mxe: Mxe,
+    ) -> (
+        MatchResult,
+        Enc<Shared, FillReceipt>,
+        Enc<Mxe, IcebergState>,
+        Enc<Shared, SwapOrder>,
+    ) {
         let o = order.to_arcis();
-        
-        // PoC Matching Logic:
-        // We check if amount_in > 0 as a basic validity check.
-        let valid = o.amount_in > 0;
-        
-        // In a real implementation, we would only return if valid.
-        // For PoC, we reveal the order details so the on-chain program can settle it.
-        o
+
+        // The order type is public on the TempWallet, so an order only gets
+        // the looser iceberg binding if it was placed as one.
+        let size_bound = if is_iceberg {
+            o.amount_in <= public_amount_in
+        } else {
+            o.amount_in == public_amount_in
+        };
+        let bound = o.nonce == public_nonce
+            && size_bound
+            && same_mint(o.token_in, [public_token_in_lo, public_token_in_hi])
+            && same_mint(o.token_out, [public_token_out_lo, public_token_out_hi]);
+        let expired = o.expires_at_slot > 0 && o.expires_at_slot < deadline_slot;
+
+        let status = if !bound {
+            STATUS_BINDING_MISMATCH
+        } else if expired {
+            STATUS_EXPIRED
+        } else if o.amount_in == 0 {
+            STATUS_ZERO_AMOUNT
+        } else if o.amount_out_min == 0 {
+            STATUS_ZERO_MIN_OUT
+        } else if same_mint(o.token_in, o.token_out) {
+            STATUS_SAME_TOKEN
+        } else if is_iceberg && o.display_size == 0 {
+            // A zero clip would reveal nothing and leave the order stuck.
+            STATUS_ZERO_DISPLAY
+        } else {
+            STATUS_ACCEPTED
+        };
+        let valid = status == STATUS_ACCEPTED;
+
+        let clip = if is_iceberg && o.display_size < o.amount_in {
+            o.display_size
+        } else {
+            o.amount_in
+        };
+
+        let result = MatchResult {
+            bound,
+            valid,
+            amount_in: if valid { clip } else { 0 },
+            token_in: if valid { o.token_in } else { [0; 2] },
+            token_out: if valid { o.token_out } else { [0; 2] },
+            nonce: o.nonce,
+            revision,
+            deadline_slot,
+            expired: bound && expired,
+        };
+
+        let receipt = FillReceipt {
+            status,
+            amount_in: if valid { o.amount_in } else { 0 },
+            amount_out_min: if valid { o.amount_out_min } else { 0 },
+        };
+
+        let iceberg = IcebergState {
+            amount_in: if valid { o.amount_in } else { 0 },
+            amount_out_min: if valid { o.amount_out_min } else { 0 },
+            display_size: o.display_size,
+        };
+
+        (
+            result.reveal(),
+            order.owner.from_arcis(receipt),
+            mxe.from_arcis(iceberg),
+            auditor.from_arcis(o),
+        )
+    }
+
+    pub struct FillCheck {
+        passed: bool,
+        nonce: u64,
+    }
+
+    pub struct TriggerCheck {
+        fire: bool,
+        nonce: u64,
+    }
+
+    // Compares a plaintext oracle price against the order's hidden trigger.
+    // `trigger_above` is true when a rising feed price favours the order.
+    // Limits and take-profits fire once the price reaches their threshold in
+    // that direction; stop-losses fire once it moves the other way past
+    // theirs. Both it and the order type are public already through the
+    // TempWallet, so only the threshold itself stays hidden.
+    #[instruction]
+    pub fn check_trigger(
+        order: Enc<Shared, SwapOrder>,
+        oracle_price: u64,
+        trigger_above: bool,
+        order_type: u8,
+    ) -> TriggerCheck {
+        let o = order.to_arcis();
+
+        let is_stop = order_type == ORDER_STOP_LOSS;
+        let threshold = if order_type == ORDER_LIMIT {
+            o.limit_price
+        } else if is_stop {
+            o.stop_price
+        } else if order_type == ORDER_TAKE_PROFIT {
+            o.take_profit_price
+        } else {
+            0
+        };
+
+        let fire_above = trigger_above != is_stop;
+        let crossed = if fire_above {
+            oracle_price >= threshold
+        } else {
+            oracle_price <= threshold
+        };
+
+        TriggerCheck {
+            fire: threshold > 0 && crossed,
+            nonce: o.nonce,
+        }
+        .reveal()
+    }
+
+    // Compares what a swap actually delivered against the still-encrypted
+    // slippage limit, revealing only whether it was met.
+    #[instruction]
+    pub fn verify_fill(order: Enc<Shared, SwapOrder>, realized_out: u64) -> FillCheck {
+        let o = order.to_arcis();
+
+        FillCheck {
+            passed: realized_out >= o.amount_out_min,
+            nonce: o.nonce,
+        }
+        .reveal()
+    }
+
+    // What is left of an order being worked in several rounds. Kept under the
+    // MXE key so neither the remaining size nor the remaining limit leaks.
+    pub struct Remainder {
+        amount_in: u64,
+        amount_out_min: u64,
+    }
+
+    pub struct RoundCheck {
+        passed: bool,
+        done: bool,
+    }
+
+    #[instruction]
+    pub fn open_remainder(order: Enc<Shared, SwapOrder>, mxe: Mxe) -> Enc<Mxe, Remainder> {
+        let o = order.to_arcis();
+        mxe.from_arcis(Remainder {
+            amount_in: o.amount_in,
+            amount_out_min: o.amount_out_min,
+        })
+    }
+
+    // Checks one round of a partially filled order. A round passes when it
+    // spent no more than what is left and got at least the pro-rata share of
+    // the remaining limit for what it spent.
+    #[instruction]
+    pub fn fill_round(
+        remainder_ctxt: Enc<Mxe, Remainder>,
+        spent_in: u64,
+        realized_out: u64,
+    ) -> (Enc<Mxe, Remainder>, RoundCheck) {
+        let r = remainder_ctxt.to_arcis();
+
+        let within = spent_in > 0 && spent_in <= r.amount_in;
+        let priced = realized_out as u128 * r.amount_in as u128
+            >= spent_in as u128 * r.amount_out_min as u128;
+        let passed = within && priced;
+
+        let spent = if passed { spent_in } else { 0 };
+        let received = if passed { realized_out } else { 0 };
+        let amount_in = r.amount_in - spent;
+        let amount_out_min = if received >= r.amount_out_min { 0 } else { r.amount_out_min - received };
+
+        let check = RoundCheck {
+            passed,
+            done: passed && amount_in == 0,
+        };
+
+        (
+            remainder_ctxt.owner.from_arcis(Remainder {
+                amount_in,
+                amount_out_min,
+            }),
+            check.reveal(),
+        )
+    }
+
+    pub struct TwapParams {
+        slices: u64,
+    }
+
+    // A TWAP schedule: what is left of the order and how many slices remain
+    // to spread it over.
+    pub struct TwapState {
+        amount_in: u64,
+        amount_out_min: u64,
+        slices_left: u64,
+    }
+
+    // `passed` covers the slice executed since the previous release; `amount`
+    // is the next slice to route, zero once the schedule is finished.
+    pub struct SliceRelease {
+        passed: bool,
+        amount: u64,
+        done: bool,
+    }
+
+    #[instruction]
+    pub fn open_twap(
+        order: Enc<Shared, SwapOrder>,
+        params: Enc<Shared, TwapParams>,
+        mxe: Mxe,
+    ) -> Enc<Mxe, TwapState> {
+        let o = order.to_arcis();
+        let p = params.to_arcis();
+
+        mxe.from_arcis(TwapState {
+            amount_in: o.amount_in,
+            amount_out_min: o.amount_out_min,
+            slices_left: if p.slices > 0 { p.slices } else { 1 },
+        })
+    }
+
+    // Checks the last slice the same way fill_round does, then sizes the next
+    // one as an even share of what is left, rounded up. The final slice takes
+    // the rest.
+    #[instruction]
+    pub fn next_slice(
+        state_ctxt: Enc<Mxe, TwapState>,
+        spent_in: u64,
+        realized_out: u64,
+    ) -> (Enc<Mxe, TwapState>, SliceRelease) {
+        let s = state_ctxt.to_arcis();
+
+        let within = spent_in <= s.amount_in;
+        let priced = realized_out as u128 * s.amount_in as u128
+            >= spent_in as u128 * s.amount_out_min as u128;
+        let passed = within && priced;
+
+        let spent = if passed { spent_in } else { 0 };
+        let received = if passed { realized_out } else { 0 };
+        let amount_in = s.amount_in - spent;
+        let amount_out_min = if received >= s.amount_out_min { 0 } else { s.amount_out_min - received };
+
+        // Rounding the share up keeps a remainder smaller than the slice
+        // count moving, and once the slices run out whatever was left
+        // unspent goes out in one piece. The schedule only ends when nothing
+        // is left to release.
+        let slices = if s.slices_left > 0 { s.slices_left } else { 1 };
+        let share = amount_in / slices + if amount_in % slices > 0 { 1 } else { 0 };
+        let amount = if share > amount_in { amount_in } else { share };
+        let amount = if passed { amount } else { 0 };
+
+        let release = SliceRelease {
+            passed,
+            amount,
+            done: passed && amount_in == 0,
+        };
+
+        (
+            state_ctxt.owner.from_arcis(TwapState {
+                amount_in,
+                amount_out_min,
+                slices_left: if s.slices_left > 0 { s.slices_left - 1 } else { 0 },
+            }),
+            release.reveal(),
+        )
+    }
+
+    pub struct ClipRelease {
+        passed: bool,
+        clip: u64,
+        done: bool,
+    }
+
+    // Checks the clip that just traded against its pro-rata share of the
+    // hidden limit and reveals the next clip, capped at what is left.
+    #[instruction]
+    pub fn refill_clip(
+        state_ctxt: Enc<Mxe, IcebergState>,
+        spent_in: u64,
+        realized_out: u64,
+    ) -> (Enc<Mxe, IcebergState>, ClipRelease) {
+        let s = state_ctxt.to_arcis();
+
+        let within = spent_in <= s.amount_in;
+        let priced = realized_out as u128 * s.amount_in as u128
+            >= spent_in as u128 * s.amount_out_min as u128;
+        let passed = within && priced;
+
+        let spent = if passed { spent_in } else { 0 };
+        let received = if passed { realized_out } else { 0 };
+        let amount_in = s.amount_in - spent;
+        let amount_out_min = if received >= s.amount_out_min { 0 } else { s.amount_out_min - received };
+
+        let clip = if s.display_size < amount_in { s.display_size } else { amount_in };
+        let clip = if passed { clip } else { 0 };
+
+        let release = ClipRelease {
+            passed,
+            clip,
+            done: passed && clip == 0,
+        };
+
+        (
+            state_ctxt.owner.from_arcis(IcebergState {
+                amount_in,
+                amount_out_min,
+                display_size: s.display_size,
+            }),
+            release.reveal(),
+        )
+    }
+
+    // Fill quantities for two orders crossed directly against each other.
+    // `fill_a` is the amount of A's token_in delivered to B and `fill_b` the
+    // amount of B's token_in delivered to A; both are zero when the orders
+    // don't cross.
+    pub struct CrossResult {
+        crossed: bool,
+        fill_a: u64,
+        fill_b: u64,
+        nonce_a: u64,
+        nonce_b: u64,
+    }
+
+    #[instruction]
+    pub fn cross_orders(
+        order_a: Enc<Shared, SwapOrder>,
+        order_b: Enc<Shared, SwapOrder>,
+        deposit_a: u64,
+        deposit_b: u64,
+    ) -> CrossResult {
+        let a = order_a.to_arcis();
+        let b = order_b.to_arcis();
+
+        // Each fill is paid out of the TempWallet deposit, so the hidden
+        // amounts have to be exactly what was deposited.
+        let bound = a.amount_in == deposit_a && b.amount_in == deposit_b;
+        let opposite_sides = same_mint(a.token_in, b.token_out)
+            && same_mint(a.token_out, b.token_in)
+            && !same_mint(a.token_in, a.token_out);
+        let non_zero = a.amount_in > 0 && b.amount_in > 0;
+        // Each side receives the other's full amount_in, so both limits must
+        // be covered by the counterparty's size.
+        let limits_met = b.amount_in >= a.amount_out_min && a.amount_in >= b.amount_out_min;
+
+        let crossed = bound && opposite_sides && non_zero && limits_met;
+
+        CrossResult {
+            crossed,
+            fill_a: if crossed { a.amount_in } else { 0 },
+            fill_b: if crossed { b.amount_in } else { 0 },
+            nonce_a: a.nonce,
+            nonce_b: b.nonce,
+        }
+        .reveal()
+    }
+
+    // Crosses an ask (A sells the base token) against a bid (B pays the quote
+    // token) at the oracle midpoint. `mid_price` is quote atoms per base atom
+    // in PRICE_SCALE fixed point. The smaller side fills completely and the
+    // other pro rata, so each order's hidden limit only has to hold as a
+    // price: A's min_out / amount_in may not exceed mid, and B's may not fall
+    // below base-per-quote at mid.
+    #[instruction]
+    pub fn match_order_mid(
+        order_a: Enc<Shared, SwapOrder>,
+        order_b: Enc<Shared, SwapOrder>,
+        deposit_a: u64,
+        deposit_b: u64,
+        mid_price: u128,
+    ) -> CrossResult {
+        let a = order_a.to_arcis();
+        let b = order_b.to_arcis();
+        let mid = if mid_price > 0 { mid_price } else { 1 };
+        let bound = a.amount_in == deposit_a && b.amount_in == deposit_b;
+
+        let opposite_sides = same_mint(a.token_in, b.token_out)
+            && same_mint(a.token_out, b.token_in)
+            && !same_mint(a.token_in, a.token_out);
+
+        let ask_in = a.amount_in as u128;
+        let bid_in = b.amount_in as u128;
+        let ask_ok = a.amount_out_min as u128 * PRICE_SCALE <= mid * ask_in;
+        let bid_ok = b.amount_out_min as u128 * mid <= bid_in * PRICE_SCALE;
+
+        let bid_wants = bid_in * PRICE_SCALE / mid;
+        let base_fill = if bid_wants < ask_in { bid_wants } else { ask_in };
+        let quote_fill = base_fill * mid / PRICE_SCALE;
+
+        let crossed = mid_price > 0
+            && bound
+            && opposite_sides
+            && ask_ok
+            && bid_ok
+            && base_fill > 0
+            && quote_fill > 0;
+
+        CrossResult {
+            crossed,
+            fill_a: if crossed { base_fill as u64 } else { 0 },
+            fill_b: if crossed { quote_fill as u64 } else { 0 },
+            nonce_a: a.nonce,
+            nonce_b: b.nonce,
+        }
+        .reveal()
+    }
+
+    // An order reduced to what pair matching needs. `is_bid` orders pay the
+    // quote token for the base token; asks go the other way. Empty and
+    // rejected slots have a zero amount_in and never fill.
+    #[derive(Copy, Clone)]
+    pub struct PairOrder {
+        amount_in: u64,
+        amount_out_min: u64,
+        is_bid: bool,
+    }
+
+    const EMPTY_ORDER: PairOrder = PairOrder {
+        amount_in: 0,
+        amount_out_min: 0,
+        is_bid: false,
+    };
+
+    // Places an order on its side of the base/quote pair, or returns an empty
+    // order when it doesn't belong to the pair or has a zero amount.
+    fn pair_order(o: &SwapOrder, base: [u128; 2], quote: [u128; 2]) -> PairOrder {
+        let is_bid = same_mint(o.token_in, quote) && same_mint(o.token_out, base);
+        let is_ask = same_mint(o.token_in, base) && same_mint(o.token_out, quote);
+        let valid = (is_bid || is_ask) && o.amount_in > 0 && o.amount_out_min > 0;
+
+        PairOrder {
+            amount_in: if valid { o.amount_in } else { 0 },
+            amount_out_min: if valid { o.amount_out_min } else { 0 },
+            is_bid,
+        }
+    }
+
+    // pair_order for an order whose deposit the program has escrowed. The
+    // order is emptied unless its encrypted size is exactly that deposit, so
+    // it can never be filled for more than it paid into the vault.
+    fn escrowed_pair_order(o: &SwapOrder, base: [u128; 2], quote: [u128; 2], deposit: u64) -> PairOrder {
+        let p = pair_order(o, base, quote);
+        let bound = o.amount_in == deposit;
+
+        PairOrder {
+            amount_in: if bound { p.amount_in } else { 0 },
+            amount_out_min: if bound { p.amount_out_min } else { 0 },
+            is_bid: p.is_bid,
+        }
+    }
+
+    pub struct BatchBook {
+        orders: [PairOrder; BATCH_SIZE],
+    }
+
+    // `filled_in` is how much of the order's token_in the auction consumed and
+    // `amount_out` how much token_out it receives. The remainder of the
+    // deposit is refunded.
+    #[derive(Copy, Clone)]
+    pub struct BatchFill {
+        filled_in: u64,
+        amount_out: u64,
+    }
+
+    pub struct BatchClearing {
+        price: u128,
+        fills: [BatchFill; BATCH_SIZE],
+    }
+
+    #[instruction]
+    pub fn open_batch(mxe: Mxe) -> Enc<Mxe, BatchBook> {
+        let book = BatchBook {
+            orders: [EMPTY_ORDER; BATCH_SIZE],
+        };
+        mxe.from_arcis(book)
+    }
+
+    #[instruction]
+    pub fn join_batch(
+        order: Enc<Shared, SwapOrder>,
+        book_ctxt: Enc<Mxe, BatchBook>,
+        slot: u8,
+        deposit: u64,
+        base_mint_lo: u128,
+        base_mint_hi: u128,
+        quote_mint_lo: u128,
+        quote_mint_hi: u128,
+    ) -> Enc<Mxe, BatchBook> {
+        let o = order.to_arcis();
+        let mut book = book_ctxt.to_arcis();
+
+        let base = [base_mint_lo, base_mint_hi];
+        let quote = [quote_mint_lo, quote_mint_hi];
+        book.orders[slot as usize] = escrowed_pair_order(&o, base, quote, deposit);
+
+        book_ctxt.owner.from_arcis(book)
+    }
+
+    #[instruction]
+    pub fn clear_batch(book_ctxt: Enc<Mxe, BatchBook>) -> BatchClearing {
+        let book = book_ctxt.to_arcis();
+
+        // Limit price of every order: the most a bid pays and the least an
+        // ask accepts, both as quote per base.
+        let mut limits = [0u128; BATCH_SIZE];
+        for i in 0..BATCH_SIZE {
+            let o = &book.orders[i];
+            let amount_in = o.amount_in as u128;
+            let min_out = o.amount_out_min as u128;
+            let amount_in_d = if amount_in > 0 { amount_in } else { 1 };
+            let min_out_d = if min_out > 0 { min_out } else { 1 };
+
+            let bid_limit = amount_in * PRICE_SCALE / min_out_d;
+            let ask_limit = (min_out * PRICE_SCALE + amount_in_d - 1) / amount_in_d;
+
+            limits[i] = if amount_in == 0 {
+                0
+            } else if o.is_bid {
+                bid_limit
+            } else {
+                ask_limit
+            };
+        }
+
+        // Every limit is a candidate price; keep the one that matches the
+        // most base volume.
+        let mut price = 0u128;
+        let mut best_volume = 0u128;
+        for c in 0..BATCH_SIZE {
+            let p = limits[c];
+            let p_d = if p > 0 { p } else { 1 };
+
+            let mut demand = 0u128;
+            let mut supply = 0u128;
+            for i in 0..BATCH_SIZE {
+                let o = &book.orders[i];
+                let live = o.amount_in > 0;
+                let bid_in = live && o.is_bid && limits[i] >= p;
+                let ask_in = live && !o.is_bid && limits[i] <= p;
+                demand += if bid_in { o.amount_in as u128 * PRICE_SCALE / p_d } else { 0 };
+                supply += if ask_in { o.amount_in as u128 } else { 0 };
+            }
+
+            let volume = if demand < supply { demand } else { supply };
+            let better = p > 0 && volume > best_volume;
+            price = if better { p } else { price };
+            best_volume = if better { volume } else { best_volume };
+        }
+
+        let p_d = if price > 0 { price } else { 1 };
+
+        // Base each eligible bid wants at the clearing price, and base each
+        // eligible ask offers.
+        let mut wanted = [0u128; BATCH_SIZE];
+        let mut offered = [0u128; BATCH_SIZE];
+        let mut demand = 0u128;
+        let mut supply = 0u128;
+        for i in 0..BATCH_SIZE {
+            let o = &book.orders[i];
+            let live = price > 0 && o.amount_in > 0;
+            let bid_in = live && o.is_bid && limits[i] >= price;
+            let ask_in = live && !o.is_bid && limits[i] <= price;
+            wanted[i] = if bid_in { o.amount_in as u128 * PRICE_SCALE / p_d } else { 0 };
+            offered[i] = if ask_in { o.amount_in as u128 } else { 0 };
+            demand += wanted[i];
+            supply += offered[i];
+        }
+        let volume = if demand < supply { demand } else { supply };
+        let demand_d = if demand > 0 { demand } else { 1 };
+
+        // Bids are rationed pro rata and rounded down, then pay for what they
+        // receive rounded up, which never exceeds their deposit.
+        let mut fills = [BatchFill {
+            filled_in: 0,
+            amount_out: 0,
+        }; BATCH_SIZE];
+        let mut base_out = 0u128;
+        let mut quote_in = 0u128;
+        for i in 0..BATCH_SIZE {
+            let received = wanted[i] * volume / demand_d;
+            let paid = (received * price + PRICE_SCALE - 1) / PRICE_SCALE;
+            let is_bid = book.orders[i].is_bid;
+            base_out += if is_bid { received } else { 0 };
+            quote_in += if is_bid { paid } else { 0 };
+            if is_bid {
+                fills[i] = BatchFill {
+                    filled_in: paid as u64,
+                    amount_out: received as u64,
+                };
+            }
+        }
+
+        // Asks cover the base handed to bids, rounded up per order, and split
+        // the quote collected from bids, rounded down, so the vaults never
+        // pay out more than they took in.
+        let supply_d = if supply > 0 { supply } else { 1 };
+        let mut given = [0u128; BATCH_SIZE];
+        let mut base_in = 0u128;
+        for i in 0..BATCH_SIZE {
+            given[i] = (offered[i] * base_out + supply_d - 1) / supply_d;
+            base_in += given[i];
+        }
+        let base_in_d = if base_in > 0 { base_in } else { 1 };
+        for i in 0..BATCH_SIZE {
+            let received = given[i] * quote_in / base_in_d;
+            if !book.orders[i].is_bid {
+                fills[i] = BatchFill {
+                    filled_in: given[i] as u64,
+                    amount_out: received as u64,
+                };
+            }
+        }
+
+        BatchClearing { price, fills }.reveal()
+    }
+
+    // Order in which a sequencing round's slots are released to execute_swap.
+    pub struct ExecutionOrder {
+        slots: [u8; BATCH_SIZE],
+    }
+
+    // Draws a uniformly random permutation of every slot inside MPC, so no
+    // keeper or single node picks who executes first. The program drops the
+    // entries past the round's count, which leaves a uniform order over the
+    // slots actually taken.
+    #[instruction]
+    pub fn shuffle_sequence() -> ExecutionOrder {
+        let mut slots = [0u8; BATCH_SIZE];
+        for i in 0..BATCH_SIZE {
+            slots[i] = i as u8;
+        }
+        ArcisRNG::shuffle(&mut slots);
+
+        ExecutionOrder { slots }.reveal()
+    }
+
+    // Running totals for one pair and epoch. Each side is counted in its own
+    // token_in: asks add base volume, bids add quote volume.
+    pub struct PairStats {
+        base_volume: u64,
+        quote_volume: u64,
+        order_count: u64,
+    }
+
+    // Band k means the total is at least 10^(k-1) and below 10^k; band 0 is
+    // zero. Volume bands stay zero for an epoch with fewer than
+    // MIN_STATS_COUNT orders, so a quiet pair can't expose one trader's size.
+    pub struct PairStatsBands {
+        base_volume_band: u8,
+        quote_volume_band: u8,
+        order_count_band: u8,
+    }
+
+    fn magnitude_band(value: u64) -> u8 {
+        let mut band = 0u8;
+        let mut threshold = 1u128;
+        for _ in 0..MAGNITUDE_BANDS {
+            band += if value as u128 >= threshold { 1 } else { 0 };
+            threshold *= 10;
+        }
+        band
+    }
+
+    #[instruction]
+    pub fn open_pair_stats(mxe: Mxe) -> Enc<Mxe, PairStats> {
+        mxe.from_arcis(PairStats {
+            base_volume: 0,
+            quote_volume: 0,
+            order_count: 0,
+        })
+    }
+
+    // Orders that don't belong to the pair or were rejected by match_order
+    // come out of pair_order empty and leave the totals untouched.
+    #[instruction]
+    pub fn record_pair_stats(
+        order: Enc<Shared, SwapOrder>,
+        stats_ctxt: Enc<Mxe, PairStats>,
+        base_mint_lo: u128,
+        base_mint_hi: u128,
+        quote_mint_lo: u128,
+        quote_mint_hi: u128,
+    ) -> Enc<Mxe, PairStats> {
+        let o = order.to_arcis();
+        let mut stats = stats_ctxt.to_arcis();
+
+        let p = pair_order(&o, [base_mint_lo, base_mint_hi], [quote_mint_lo, quote_mint_hi]);
+        let counted = p.amount_in > 0;
+        stats.base_volume += if counted && !p.is_bid { p.amount_in } else { 0 };
+        stats.quote_volume += if counted && p.is_bid { p.amount_in } else { 0 };
+        stats.order_count += if counted { 1 } else { 0 };
+
+        stats_ctxt.owner.from_arcis(stats)
+    }
+
+    #[instruction]
+    pub fn reveal_pair_stats(stats_ctxt: Enc<Mxe, PairStats>) -> PairStatsBands {
+        let stats = stats_ctxt.to_arcis();
+        let enough = stats.order_count >= MIN_STATS_COUNT;
+
+        let bands = PairStatsBands {
+            base_volume_band: if enough { magnitude_band(stats.base_volume) } else { 0 },
+            quote_volume_band: if enough { magnitude_band(stats.quote_volume) } else { 0 },
+            order_count_band: magnitude_band(stats.order_count),
+        };
+
+        bands.reveal()
+    }
+
+    // Resting orders for one pair, held by the MXE between matches.
+    pub struct OrderBook {
+        orders: [PairOrder; BOOK_CAPACITY],
+    }
+
+    // Outcome of a taker order against the book. The taker trades at the
+    // resting order's price: it gives `taker_gives` of its token_in and
+    // receives `taker_receives` of its token_out from the maker in `slot`.
+    pub struct BookFill {
+        matched: bool,
+        slot: u8,
+        taker_gives: u64,
+        taker_receives: u64,
+    }
+
+    #[instruction]
+    pub fn book_open(mxe: Mxe) -> Enc<Mxe, OrderBook> {
+        let book = OrderBook {
+            orders: [EMPTY_ORDER; BOOK_CAPACITY],
+        };
+        mxe.from_arcis(book)
+    }
+
+    #[instruction]
+    pub fn book_insert(
+        order: Enc<Shared, SwapOrder>,
+        book_ctxt: Enc<Mxe, OrderBook>,
+        slot: u8,
+        deposit: u64,
+        base_mint_lo: u128,
+        base_mint_hi: u128,
+        quote_mint_lo: u128,
+        quote_mint_hi: u128,
+    ) -> (Enc<Mxe, OrderBook>, bool) {
+        let o = order.to_arcis();
+        let mut book = book_ctxt.to_arcis();
+
+        let resting = escrowed_pair_order(
+            &o,
+            [base_mint_lo, base_mint_hi],
+            [quote_mint_lo, quote_mint_hi],
+            deposit,
+        );
+        let accepted = resting.amount_in > 0;
+        book.orders[slot as usize] = resting;
+
+        (book_ctxt.owner.from_arcis(book), accepted.reveal())
+    }
+
+    #[instruction]
+    pub fn book_cancel(book_ctxt: Enc<Mxe, OrderBook>, slot: u8) -> Enc<Mxe, OrderBook> {
+        let mut book = book_ctxt.to_arcis();
+        book.orders[slot as usize] = EMPTY_ORDER;
+        book_ctxt.owner.from_arcis(book)
+    }
+
+    #[instruction]
+    pub fn book_match(
+        order: Enc<Shared, SwapOrder>,
+        book_ctxt: Enc<Mxe, OrderBook>,
+        deposit: u64,
+        base_mint_lo: u128,
+        base_mint_hi: u128,
+        quote_mint_lo: u128,
+        quote_mint_hi: u128,
+    ) -> (Enc<Mxe, OrderBook>, BookFill) {
+        let o = order.to_arcis();
+        let mut book = book_ctxt.to_arcis();
+
+        let taker = escrowed_pair_order(
+            &o,
+            [base_mint_lo, base_mint_hi],
+            [quote_mint_lo, quote_mint_hi],
+            deposit,
+        );
+        let taker_live = taker.amount_in > 0;
+
+        // Pick the resting order on the other side that gives the taker the
+        // most per unit while still covering its amount_out_min.
+        let mut matched = false;
+        let mut slot = 0u8;
+        let mut best_in = 0u64;
+        let mut best_min = 0u64;
+        let mut taker_gives = 0u64;
+        let mut taker_receives = 0u64;
+        for i in 0..BOOK_CAPACITY {
+            let m = book.orders[i];
+            let live = taker_live && m.amount_in > 0 && m.is_bid != taker.is_bid;
+            let min_d = if m.amount_out_min > 0 { m.amount_out_min } else { 1 };
+
+            let gives = if taker.amount_in < m.amount_out_min { taker.amount_in } else { m.amount_out_min };
+            let receives = (gives as u128 * m.amount_in as u128 / min_d as u128) as u64;
+            let crosses = live && receives >= taker.amount_out_min;
+
+            let better = !matched
+                || m.amount_in as u128 * best_min as u128 > best_in as u128 * m.amount_out_min as u128;
+            let take = crosses && better;
+
+            matched = matched || take;
+            slot = if take { i as u8 } else { slot };
+            best_in = if take { m.amount_in } else { best_in };
+            best_min = if take { m.amount_out_min } else { best_min };
+            taker_gives = if take { gives } else { taker_gives };
+            taker_receives = if take { receives } else { taker_receives };
+        }
+
+        // Shrink the maker by what it traded. Once nothing is left to receive
+        // the order is done, so drop any rounding leftover with it.
+        for i in 0..BOOK_CAPACITY {
+            let m = book.orders[i];
+            let hit = matched && slot == i as u8;
+            let in_left = m.amount_in - if hit { taker_receives } else { 0 };
+            let min_left = m.amount_out_min - if hit { taker_gives } else { 0 };
+            book.orders[i] = PairOrder {
+                amount_in: if min_left == 0 { 0 } else { in_left },
+                amount_out_min: min_left,
+                is_bid: m.is_bid,
+            };
+        }
+
+        let fill = BookFill {
+            matched,
+            slot,
+            taker_gives,
+            taker_receives,
+        };
+
+        (book_ctxt.owner.from_arcis(book), fill.reveal())
+    }
+
+    // A market maker's sealed offer: how much of the taker's token_out it
+    // pays for the taker's full amount_in.
+    pub struct Quote {
+        amount_out: u64,
+    }
+
+    pub struct RfqResult {
+        filled: bool,
+        winner: u8,
+        amount_out: u64,
+    }
+
+    // Picks the best quote that covers the taker's hidden amount_out_min.
+    // Quotes past `count` are empty slots, and a quote larger than its maker's
+    // escrow balance could never settle, so both are skipped. Only the winner
+    // and its price come out.
+    #[instruction]
+    pub fn select_quote(
+        order: Enc<Shared, SwapOrder>,
+        public_amount_in: u64,
+        public_token_in_lo: u128,
+        public_token_in_hi: u128,
+        public_token_out_lo: u128,
+        public_token_out_hi: u128,
+        quote_0: Enc<Shared, Quote>,
+        quote_1: Enc<Shared, Quote>,
+        quote_2: Enc<Shared, Quote>,
+        quote_3: Enc<Shared, Quote>,
+        balance_0: u64,
+        balance_1: u64,
+        balance_2: u64,
+        balance_3: u64,
+        count: u8,
+    ) -> RfqResult {
+        let o = order.to_arcis();
+
+        let bound = o.amount_in == public_amount_in
+            && o.amount_in > 0
+            && same_mint(o.token_in, [public_token_in_lo, public_token_in_hi])
+            && same_mint(o.token_out, [public_token_out_lo, public_token_out_hi]);
+
+        let quotes = [
+            quote_0.to_arcis().amount_out,
+            quote_1.to_arcis().amount_out,
+            quote_2.to_arcis().amount_out,
+            quote_3.to_arcis().amount_out,
+        ];
+        let balances = [balance_0, balance_1, balance_2, balance_3];
+
+        let mut filled = false;
+        let mut winner = 0u8;
+        let mut best = 0u64;
+        for i in 0..RFQ_QUOTES {
+            let eligible = bound
+                && (i as u8) < count
+                && quotes[i] >= o.amount_out_min
+                && quotes[i] <= balances[i];
+            let take = eligible && (!filled || quotes[i] > best);
+
+            filled = filled || take;
+            winner = if take { i as u8 } else { winner };
+            best = if take { quotes[i] } else { best };
+        }
+
+        RfqResult {
+            filled,
+            winner,
+            amount_out: best,
+        }
+        .reveal()
+    }
+
+    // One side of a negotiated block trade: what it hands over and the least
+    // it will accept in return.
+    pub struct OtcTerms {
+        give: u64,
+        want_min: u64,
+    }
+
+    pub struct OtcMatch {
+        compatible: bool,
+        amount_a: u64,
+        amount_b: u64,
+    }
+
+    // Two sets of terms agree when each side gives at least what the other
+    // wants and no more than it escrowed. Amounts are revealed only for a
+    // compatible pair, since settlement has to move them in the clear anyway.
+    #[instruction]
+    pub fn check_otc_terms(
+        terms_a: Enc<Shared, OtcTerms>,
+        terms_b: Enc<Shared, OtcTerms>,
+        deposit_a: u64,
+        deposit_b: u64,
+    ) -> OtcMatch {
+        let a = terms_a.to_arcis();
+        let b = terms_b.to_arcis();
+
+        let compatible = a.give > 0
+            && b.give > 0
+            && a.give <= deposit_a
+            && b.give <= deposit_b
+            && a.give >= b.want_min
+            && b.give >= a.want_min;
+
+        OtcMatch {
+            compatible,
+            amount_a: if compatible { a.give } else { 0 },
+            amount_b: if compatible { b.give } else { 0 },
+        }
+        .reveal()
+    }
+
+    // A user's shielded balance of one mint, held in the program vault.
+    pub struct Balance {
+        amount: u64,
+    }
+
+    #[instruction]
+    pub fn open_balance(mxe: Mxe) -> Enc<Mxe, Balance> {
+        mxe.from_arcis(Balance { amount: 0 })
+    }
+
+    // Deposits are public on the way into the vault, so the amount is
+    // plaintext; only the running total stays hidden. A balance can't hold
+    // more than the mint's supply, so the total saturates rather than wrapping
+    // back to nothing.
+    #[instruction]
+    pub fn credit_balance(balance_ctxt: Enc<Mxe, Balance>, amount: u64) -> Enc<Mxe, Balance> {
+        let b = balance_ctxt.to_arcis();
+        let total = b.amount as u128 + amount as u128;
+
+        balance_ctxt.owner.from_arcis(Balance {
+            amount: if total > u64::MAX as u128 { u64::MAX } else { total as u64 },
+        })
+    }
+
+    // Withdraws `amount` if the balance covers it. Only the success flag is
+    // revealed; the remaining balance is not.
+    #[instruction]
+    pub fn debit_balance(balance_ctxt: Enc<Mxe, Balance>, amount: u64) -> (Enc<Mxe, Balance>, bool) {
+        let b = balance_ctxt.to_arcis();
+        let ok = amount <= b.amount;
+
+        (
+            balance_ctxt.owner.from_arcis(Balance {
+                amount: if ok { b.amount - amount } else { b.amount },
+            }),
+            ok.reveal(),
+        )
+    }
+
+    pub struct TransferAmount {
+        amount: u64,
+    }
+
+    // Moves a sender-encrypted amount between two shielded balances of the
+    // same mint. Neither the amount nor either balance is revealed; a
+    // transfer the sender can't cover, or the recipient's balance can't hold,
+    // moves nothing and reports failure.
+    #[instruction]
+    pub fn private_transfer(
+        amount_ctxt: Enc<Shared, TransferAmount>,
+        from_ctxt: Enc<Mxe, Balance>,
+        to_ctxt: Enc<Mxe, Balance>,
+    ) -> (Enc<Mxe, Balance>, Enc<Mxe, Balance>, bool) {
+        let amount = amount_ctxt.to_arcis().amount;
+        let from = from_ctxt.to_arcis();
+        let to = to_ctxt.to_arcis();
+
+        let ok = amount > 0
+            && amount <= from.amount
+            && to.amount as u128 + amount as u128 <= u64::MAX as u128;
+        let moved = if ok { amount } else { 0 };
+
+        (
+            from_ctxt.owner.from_arcis(Balance {
+                amount: from.amount - moved,
+            }),
+            to_ctxt.owner.from_arcis(Balance {
+                amount: to.amount + moved,
+            }),
+            ok.reveal(),
+        )
+    }
+
+    // An order against shielded balances. The mints are public on the
+    // ShieldedOrder account, so only the sizes are encrypted.
+    pub struct ShieldedOrder {
+        amount_in: u64,
+        amount_out_min: u64,
+    }
+
+    // Crosses two shielded orders inside the vault, on the same terms as
+    // cross_orders: each side receives the other's full amount_in. Each
+    // side's token_in balance must cover its order. Balances move only when
+    // both orders agree, and nothing but the outcome is revealed.
+    #[instruction]
+    pub fn cross_shielded(
+        order_a: Enc<Shared, ShieldedOrder>,
+        order_b: Enc<Shared, ShieldedOrder>,
+        a_in_ctxt: Enc<Mxe, Balance>,
+        a_out_ctxt: Enc<Mxe, Balance>,
+        b_in_ctxt: Enc<Mxe, Balance>,
+        b_out_ctxt: Enc<Mxe, Balance>,
+    ) -> (
+        Enc<Mxe, Balance>,
+        Enc<Mxe, Balance>,
+        Enc<Mxe, Balance>,
+        Enc<Mxe, Balance>,
+        bool,
+    ) {
+        let a = order_a.to_arcis();
+        let b = order_b.to_arcis();
+        let a_in = a_in_ctxt.to_arcis();
+        let a_out = a_out_ctxt.to_arcis();
+        let b_in = b_in_ctxt.to_arcis();
+        let b_out = b_out_ctxt.to_arcis();
+
+        let crossed = a.amount_in > 0
+            && b.amount_in > 0
+            && b.amount_in >= a.amount_out_min
+            && a.amount_in >= b.amount_out_min
+            && a.amount_in <= a_in.amount
+            && b.amount_in <= b_in.amount
+            && a_out.amount as u128 + b.amount_in as u128 <= u64::MAX as u128
+            && b_out.amount as u128 + a.amount_in as u128 <= u64::MAX as u128;
+
+        let give_a = if crossed { a.amount_in } else { 0 };
+        let give_b = if crossed { b.amount_in } else { 0 };
+
+        (
+            a_in_ctxt.owner.from_arcis(Balance {
+                amount: a_in.amount - give_a,
+            }),
+            a_out_ctxt.owner.from_arcis(Balance {
+                amount: a_out.amount + give_b,
+            }),
+            b_in_ctxt.owner.from_arcis(Balance {
+                amount: b_in.amount - give_b,
+            }),
+            b_out_ctxt.owner.from_arcis(Balance {
+                amount: b_out.amount + give_a,
+            }),
+            crossed.reveal(),
+        )
+    }
+
+    // A trader's output volume in one mint over the current and previous fee
+    // epochs; their sum is the trailing volume that picks the fee tier.
+    pub struct TrailingVolume {
+        current: u64,
+        previous: u64,
+    }
+
+    // Charges the fee on `amount` of new output at the tier the trailing
+    // volume reaches, then adds `amount` to the volume. The schedule itself
+    // is public; only the fee comes out. `fresh` marks a volume account with
+    // no state yet, and `epochs_elapsed` rolls the window forward.
+    #[instruction]
+    pub fn compute_fee(
+        volume_ctxt: Enc<Mxe, TrailingVolume>,
+        fresh: bool,
+        epochs_elapsed: u64,
+        amount: u64,
+        threshold_1: u64,
+        threshold_2: u64,
+        threshold_3: u64,
+        bps_0: u64,
+        bps_1: u64,
+        bps_2: u64,
+        bps_3: u64,
+    ) -> (Enc<Mxe, TrailingVolume>, u64) {
+        let v = volume_ctxt.to_arcis();
+
+        let (current, previous) = if fresh || epochs_elapsed >= 2 {
+            (0, 0)
+        } else if epochs_elapsed == 1 {
+            (0, v.current)
+        } else {
+            (v.current, v.previous)
+        };
+        let trailing = current as u128 + previous as u128;
+
+        let bps = if trailing >= threshold_3 as u128 {
+            bps_3
+        } else if trailing >= threshold_2 as u128 {
+            bps_2
+        } else if trailing >= threshold_1 as u128 {
+            bps_1
+        } else {
+            bps_0
+        };
+        let fee = (amount as u128 * bps as u128 / 10_000) as u64;
+
+        // Saturates rather than wrapping, which would drop a heavy trader
+        // back into the lowest-volume tier.
+        let total = current as u128 + amount as u128;
+        let current = if total > u64::MAX as u128 { u64::MAX } else { total as u64 };
+
+        (
+            volume_ctxt.owner.from_arcis(TrailingVolume {
+                current,
+                previous,
+            }),
+            fee.reveal(),
+        )
+    }
+
+    // A permissioned pool's admission rule: the jurisdiction codes it accepts,
+    // with zero marking an unused entry, and the lowest tier it lets in.
+    pub struct AccessPolicy {
+        jurisdictions: [u64; POLICY_JURISDICTIONS],
+        min_tier: u64,
+    }
+
+    // What the policy's attester vouches for about one user.
+    pub struct Attestation {
+        jurisdiction: u64,
+        tier: u64,
+    }
+
+    #[instruction]
+    pub fn store_policy(policy: Enc<Shared, AccessPolicy>, mxe: Mxe) -> Enc<Mxe, AccessPolicy> {
+        mxe.from_arcis(policy.to_arcis())
+    }
+
+    // Only the verdict is revealed; neither the user's jurisdiction and tier
+    // nor the policy's contents leave MPC.
+    #[instruction]
+    pub fn check_access(
+        attestation: Enc<Shared, Attestation>,
+        policy_ctxt: Enc<Mxe, AccessPolicy>,
+    ) -> bool {
+        let a = attestation.to_arcis();
+        let p = policy_ctxt.to_arcis();
+
+        let mut listed = false;
+        for i in 0..POLICY_JURISDICTIONS {
+            let code = p.jurisdictions[i];
+            listed = listed || (code != 0 && code == a.jurisdiction);
+        }
+
+        (listed && a.tier >= p.min_tier).reveal()
     }
 }
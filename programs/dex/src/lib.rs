@@ -1,9 +1,118 @@
 use anchor_lang::prelude::*;
 use arcium_anchor::prelude::*;
 use anchor_spl::token::{self, Token, TokenAccount, Mint, Transfer, CloseAccount};
-use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
 
 const COMP_DEF_OFFSET_MATCH_ORDER: u32 = comp_def_offset("match_order");
+const COMP_DEF_OFFSET_MATCH_ORDER_MID: u32 = comp_def_offset("match_order_mid");
+const COMP_DEF_OFFSET_VERIFY_FILL: u32 = comp_def_offset("verify_fill");
+const COMP_DEF_OFFSET_CROSS_ORDERS: u32 = comp_def_offset("cross_orders");
+const COMP_DEF_OFFSET_OPEN_BATCH: u32 = comp_def_offset("open_batch");
+const COMP_DEF_OFFSET_JOIN_BATCH: u32 = comp_def_offset("join_batch");
+const COMP_DEF_OFFSET_CLEAR_BATCH: u32 = comp_def_offset("clear_batch");
+const COMP_DEF_OFFSET_SHUFFLE_SEQUENCE: u32 = comp_def_offset("shuffle_sequence");
+const COMP_DEF_OFFSET_OPEN_PAIR_STATS: u32 = comp_def_offset("open_pair_stats");
+const COMP_DEF_OFFSET_RECORD_PAIR_STATS: u32 = comp_def_offset("record_pair_stats");
+const COMP_DEF_OFFSET_REVEAL_PAIR_STATS: u32 = comp_def_offset("reveal_pair_stats");
+const COMP_DEF_OFFSET_STORE_POLICY: u32 = comp_def_offset("store_policy");
+const COMP_DEF_OFFSET_CHECK_ACCESS: u32 = comp_def_offset("check_access");
+const COMP_DEF_OFFSET_BOOK_OPEN: u32 = comp_def_offset("book_open");
+const COMP_DEF_OFFSET_BOOK_INSERT: u32 = comp_def_offset("book_insert");
+const COMP_DEF_OFFSET_BOOK_CANCEL: u32 = comp_def_offset("book_cancel");
+const COMP_DEF_OFFSET_BOOK_MATCH: u32 = comp_def_offset("book_match");
+const COMP_DEF_OFFSET_OPEN_REMAINDER: u32 = comp_def_offset("open_remainder");
+const COMP_DEF_OFFSET_FILL_ROUND: u32 = comp_def_offset("fill_round");
+const COMP_DEF_OFFSET_CHECK_TRIGGER: u32 = comp_def_offset("check_trigger");
+const COMP_DEF_OFFSET_OPEN_TWAP: u32 = comp_def_offset("open_twap");
+const COMP_DEF_OFFSET_NEXT_SLICE: u32 = comp_def_offset("next_slice");
+const COMP_DEF_OFFSET_REFILL_CLIP: u32 = comp_def_offset("refill_clip");
+const COMP_DEF_OFFSET_SELECT_QUOTE: u32 = comp_def_offset("select_quote");
+const COMP_DEF_OFFSET_CHECK_OTC_TERMS: u32 = comp_def_offset("check_otc_terms");
+const COMP_DEF_OFFSET_OPEN_BALANCE: u32 = comp_def_offset("open_balance");
+const COMP_DEF_OFFSET_CREDIT_BALANCE: u32 = comp_def_offset("credit_balance");
+const COMP_DEF_OFFSET_DEBIT_BALANCE: u32 = comp_def_offset("debit_balance");
+const COMP_DEF_OFFSET_CROSS_SHIELDED: u32 = comp_def_offset("cross_shielded");
+const COMP_DEF_OFFSET_PRIVATE_TRANSFER: u32 = comp_def_offset("private_transfer");
+const COMP_DEF_OFFSET_COMPUTE_FEE: u32 = comp_def_offset("compute_fee");
+
+// Must match BATCH_SIZE in encrypted-ixs; each BatchOrder is three ciphertexts.
+pub const BATCH_SIZE: usize = 8;
+pub const BATCH_BOOK_CIPHERTEXTS: usize = 3 * BATCH_SIZE;
+pub const BATCH_EPOCH_SLOTS: u64 = 150;
+// A batch computation that hasn't come back within this many slots can be
+// aborted, which refunds every deposit in the batch.
+pub const BATCH_PENDING_TIMEOUT_SLOTS: u64 = 300;
+// Sequencing rounds share the batch size and epoch. A released order that
+// hasn't executed within this many slots loses its turn to the next one.
+pub const SEQUENCE_TURN_SLOTS: u64 = 50;
+
+// Must match PairStats in encrypted-ixs. Stats epochs run roughly a day.
+pub const PAIR_STATS_CIPHERTEXTS: usize = 3;
+pub const STATS_EPOCH_SLOTS: u64 = 216_000;
+
+// Must match BOOK_CAPACITY in encrypted-ixs; each resting order is three ciphertexts.
+pub const BOOK_CAPACITY: usize = 16;
+pub const BOOK_CIPHERTEXTS: usize = 3 * BOOK_CAPACITY;
+// Same as batches: an insert, cancel or match that hasn't come back within
+// this many slots can be released by anyone.
+pub const BOOK_PENDING_TIMEOUT_SLOTS: u64 = 300;
+
+// Must match FillReceipt in encrypted-ixs.
+pub const FILL_RECEIPT_CIPHERTEXTS: usize = 3;
+
+// Must match SwapOrder in encrypted-ixs; each mint is two limbs.
+pub const SWAP_ORDER_CIPHERTEXTS: usize = 12;
+
+// Must match AccessPolicy and Attestation in encrypted-ixs.
+pub const POLICY_JURISDICTIONS: usize = 8;
+pub const ACCESS_POLICY_CIPHERTEXTS: usize = POLICY_JURISDICTIONS + 1;
+pub const ATTESTATION_CIPHERTEXTS: usize = 2;
+
+// Must match Remainder in encrypted-ixs.
+pub const REMAINDER_CIPHERTEXTS: usize = 2;
+
+// Must match TwapState in encrypted-ixs.
+pub const TWAP_STATE_CIPHERTEXTS: usize = 3;
+
+// Must match IcebergState in encrypted-ixs.
+pub const ICEBERG_STATE_CIPHERTEXTS: usize = 3;
+
+// Must match RFQ_QUOTES in encrypted-ixs.
+pub const RFQ_QUOTES: usize = 4;
+
+// Must match Balance in encrypted-ixs.
+pub const BALANCE_CIPHERTEXTS: usize = 1;
+// A shielded balance or order whose computation hasn't come back within this
+// many slots can be released.
+pub const SHIELDED_PENDING_TIMEOUT_SLOTS: u64 = 300;
+
+// Must match TrailingVolume in encrypted-ixs, and the four tiers compute_fee
+// takes.
+pub const VOLUME_CIPHERTEXTS: usize = 2;
+pub const FEE_TIERS: usize = 4;
+// Trailing volume covers the current and previous epoch, roughly a day each.
+pub const FEE_EPOCH_SLOTS: u64 = 216_000;
+pub const MAX_FEE_BPS: u16 = 100;
+// How long a queued match_order may take to come back before its result is
+// dropped, roughly a minute.
+pub const MAX_MATCH_LATENCY_SLOTS: u64 = 150;
+
+// Jupiter v6. execute_swap and unwind_failed_fill sign for the TempWallet, so
+// they may only route through it.
+pub const JUPITER_PROGRAM_ID: Pubkey =
+    anchor_lang::solana_program::pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+// Legacy Pyth price account layout.
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_STATUS_TRADING: u32 = 1;
+pub const MAX_ORACLE_AGE_SLOTS: u64 = 25;
+
+// A pool's new access policy takes effect this long after set_pool_policy,
+// roughly an hour, so orders admitted under the old one can still get in.
+pub const POOL_POLICY_NOTICE_SLOTS: u64 = 9_000;
+
+// Must match PRICE_SCALE (10^9) in encrypted-ixs.
+const PRICE_SCALE_DECIMALS: i32 = 9;
 
 declare_id!("5XQ8wk4T8haHVRBFF1XBnNUUifyXiv4WUTvnGC2P4oVo");
 
@@ -16,61 +125,19 @@ pub mod dex {
         Ok(())
     }
 
-    pub fn place_order(
-        ctx: Context<PlaceOrder>,
-        computation_offset: u64,
-        amount_in: [u8; 32],
-        amount_out_min: [u8; 32],
-        token_in: [u8; 32],
-        token_out: [u8; 32],
-        nonce: [u8; 32],
-        public_nonce: u64,
-    ) -> Result<()> {
-        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
-        
-        // Initialize the settlement request
-        let settlement = &mut ctx.accounts.settlement_request;
-        settlement.active = false; // Not ready yet
-        settlement.nonce = public_nonce;
-        settlement.bump = ctx.bumps.settlement_request;
-
-        let args = vec![
-            Argument::EncryptedU64(amount_in),
-            Argument::EncryptedU64(amount_out_min),
-            Argument::EncryptedU64(token_in),
-            Argument::EncryptedU64(token_out),
-            Argument::EncryptedU64(nonce),
-        ];
-
-        queue_computation(
-            ctx.accounts,
-            computation_offset,
-            args,
-            None,
-            vec![MatchOrderCallback::callback_ix(&[])],
-        )?;
-
-        Ok(())
-    }
-
     #[arcium_callback(encrypted_ix = "match_order", auto_serialize = false)]
     pub fn match_order_callback(
         ctx: Context<MatchOrderCallback>,
-        output: ComputationOutputs<SwapOrder>,
+        output: ComputationOutputs<MatchOrderOutput>,
     ) -> Result<()> {
-        let o = match output {
-            ComputationOutputs::Success(o) => o,
+        let MatchOrderOutput { result: o, receipt, iceberg, disclosure } = match output {
+            ComputationOutputs::Success(out) => out,
             _ => return Err(ErrorCode::AbortedComputation.into()),
         };
 
-        // field_0 is the SwapOrder struct (plaintext)
-        let amount_in = o.amount_in;
-        let amount_out_min = o.amount_out_min;
-        let token_in = o.token_in;
-        let token_out = o.token_out;
-        let nonce = o.nonce;
+        require!(o.bound, ErrorCode::OrderBindingMismatch);
 
-        require!(amount_in > 0, ErrorCode::InvalidOrderAmount);
+        let nonce = o.nonce;
 
         // Verify the settlement_request PDA matches the nonce from the circuit
         let (pda, _bump) = Pubkey::find_program_address(
@@ -79,32 +146,123 @@ pub mod dex {
         );
         require_keys_eq!(ctx.accounts.settlement_request.key(), pda, ErrorCode::InvalidSettlementPDA);
 
+        let (receipt_pda, _bump) = Pubkey::find_program_address(
+            &[b"receipt", ctx.accounts.temp_wallet.key().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.order_receipt.key(), receipt_pda, ErrorCode::InvalidReceiptPDA);
+
+        let (disclosure_pda, _bump) = Pubkey::find_program_address(
+            &[b"disclosure", ctx.accounts.temp_wallet.key().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.order_disclosure.key(), disclosure_pda, ErrorCode::InvalidDisclosurePDA);
+
+        // amend_order re-queued this order with new ciphertexts; a later
+        // callback carries the current result.
+        if o.revision != ctx.accounts.temp_wallet.revision {
+            msg!("Ignoring match result for superseded revision {}", o.revision);
+            return Ok(());
+        }
+
+        // The order's expiry was checked against this deadline, so a result
+        // arriving after it could settle an order past its expiry.
+        if Clock::get()?.slot > o.deadline_slot {
+            emit!(OrderExpiredEvent { nonce, late: true });
+            msg!("Match result arrived after slot {}", o.deadline_slot);
+            return Ok(());
+        }
+
+        // Only the submitter's x25519 key can open this, including the reason
+        // for a rejection.
+        let order_receipt = &mut ctx.accounts.order_receipt;
+        order_receipt.encryption_key = receipt.encryption_key;
+        order_receipt.encryption_nonce = receipt.nonce;
+        order_receipt.ciphertexts = receipt.ciphertexts;
+
+        // Stored only when the order was queued with auditing on; otherwise
+        // the copy went to the submitter's own key and is dropped.
+        let order_disclosure = &mut ctx.accounts.order_disclosure;
+        if order_disclosure.auditor_key != [0; 32] && order_disclosure.auditor_key == disclosure.encryption_key {
+            order_disclosure.encryption_nonce = disclosure.nonce;
+            order_disclosure.ciphertexts = disclosure.ciphertexts;
+            order_disclosure.revision = o.revision;
+        }
+
+        if o.expired {
+            emit!(OrderExpiredEvent { nonce, late: false });
+            msg!("Order expired before it could be matched");
+            return Ok(());
+        }
+
+        // The circuit zeroes every field of a rejected order, so there is nothing
+        // to settle. Leave the request inactive and let the user reclaim funds.
+        if !o.valid {
+            emit!(OrderRejectedEvent { nonce });
+            msg!("Order rejected by MPC validation");
+            return Ok(());
+        }
+
+        let amount_in = o.amount_in;
+        let token_in = mint_from_limbs(o.token_in);
+        let token_out = mint_from_limbs(o.token_out);
+
+        require!(amount_in > 0, ErrorCode::InvalidOrderAmount);
+
+        // The encrypted order names its mints in full, so they can be checked
+        // against the TempWallet directly instead of through the token registry.
+        let temp = &ctx.accounts.temp_wallet;
+        require!(temp.nonce == nonce, ErrorCode::InvalidSettlementPDA);
+        require_keys_eq!(temp.token_in_mint, token_in, ErrorCode::MintMismatch);
+        require_keys_eq!(temp.token_out_mint, token_out, ErrorCode::MintMismatch);
+
         // Update the settlement request
         // We need to manually deserialize since it's an UncheckedAccount
         let mut data = ctx.accounts.settlement_request.try_borrow_mut_data()?;
         let mut settlement: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
         
         settlement.amount_in = amount_in;
-        settlement.min_out = amount_out_min;
         settlement.token_in = token_in;
         settlement.token_out = token_out;
         settlement.nonce = nonce;
-        settlement.active = true;
+
+        settlement.encrypted_iceberg = iceberg.ciphertexts;
+        settlement.iceberg_nonce = iceberg.nonce;
+
+        // Limit, stop-loss and take-profit orders wait for check_trigger to see the oracle cross their
+        // hidden price before the keeper may route them.
+        // TWAP orders stay inactive too; their slices are released by
+        // release_twap_slice once start_twap has set up the schedule.
+        let is_triggered = temp.order_type.uses_price_feed();
+        let is_armed = temp.order_type.waits_for_release();
+        settlement.active = !is_armed;
+        settlement.awaiting_trigger = is_triggered;
+
+        // An iceberg only ever shows its clip; amount_in is already capped to
+        // it by the circuit, and execute_swap may not spend past it.
+        if temp.order_type == OrderType::Iceberg {
+            settlement.slice_amount = amount_in;
+        }
         
         // Serialize back
         settlement.try_serialize(&mut *data)?;
 
+        if is_armed {
+            emit!(OrderArmedEvent {
+                nonce,
+                order_type: temp.order_type,
+                price_feed: temp.price_feed,
+            });
+            return Ok(());
+        }
+
         emit!(OrderSettledEvent {
             amount_in,
-            min_out: amount_out_min,
             token_in,
             token_out,
             nonce,
         });
 
-        msg!("Order Settled (Plaintext): In: {}, Min Out: {}, Token In ID: {}, Token Out ID: {}", 
-             amount_in, amount_out_min, token_in, token_out);
-
         Ok(())
     }
 
@@ -114,50 +272,39 @@ pub mod dex {
     ) -> Result<()> {
         let settlement = &mut ctx.accounts.settlement_request;
         require!(settlement.active, ErrorCode::SettlementNotActive);
+        require!(
+            !ctx.accounts.temp_wallet.order_type.waits_for_release() || settlement.triggered,
+            ErrorCode::TriggerNotPassed
+        );
+        if settlement.partial {
+            require!(Clock::get()?.slot <= settlement.expires_at_slot, ErrorCode::PartialFillExpired);
+        }
         
-        // Validation: 
-        // In a real implementation, we would inspect `data` to ensure `min_out` matches settlement.min_out
-        // For now, we trust the keeper/user to provide the correct route that yields >= min_out.
-        // If the swap fails (slippage), the transaction reverts.
+        // The user's min_out stays encrypted, so the route can't be checked
+        // here. Instead we record what it actually delivered and have
+        // `verify_fill` compare that against the limit inside MPC.
+        let balance_before = ctx.accounts.temp_token_account_out.amount;
+        let spent_before = ctx.accounts.temp_token_account_in.amount;
         
         // Mark as inactive/filled to prevent replay
         settlement.active = false;
         
-        // Construct the instruction for Jupiter CPI
-        // We pass through all remaining accounts to Jupiter
-        let mut accounts = vec![];
-        for acc in ctx.remaining_accounts.iter() {
-            accounts.push(AccountMeta {
-                pubkey: *acc.key,
-                is_signer: acc.is_signer,
-                is_writable: acc.is_writable,
-            });
-        }
-        
-        let jupiter_program = ctx.accounts.jupiter_program.key();
-        
-        let ix = anchor_lang::solana_program::instruction::Instruction {
-            program_id: jupiter_program,
-            accounts,
-            data,
-        };
-        
-        // Invoke Jupiter with PDA signing if needed (temp wallet custody)
-        let bump = ctx.accounts.temp_wallet.bump;
-        let bump_arr = [bump];
-        let nonce_bytes = ctx.accounts.temp_wallet.nonce.to_le_bytes();
-        let seeds = &[
-            b"temp_wallet",
-            ctx.accounts.temp_wallet.user.as_ref(),
-            nonce_bytes.as_ref(),
-            &bump_arr[..],
-        ];
-        let signer = &[&seeds[..]];
-        anchor_lang::solana_program::program::invoke_signed(
-            &ix,
+        invoke_jupiter(
+            ctx.accounts.jupiter_program.key(),
             ctx.remaining_accounts,
-            signer,
+            data,
+            &ctx.accounts.temp_wallet,
         )?;
+
+        ctx.accounts.temp_token_account_out.reload()?;
+        let balance_after = ctx.accounts.temp_token_account_out.amount;
+        settlement.realized_out = balance_after.saturating_sub(balance_before);
+        ctx.accounts.temp_token_account_in.reload()?;
+        settlement.spent_in = spent_before.saturating_sub(ctx.accounts.temp_token_account_in.amount);
+        if settlement.slice_amount > 0 {
+            require!(settlement.spent_in <= settlement.slice_amount, ErrorCode::TwapSliceExceeded);
+        }
+        settlement.fill_status = FillStatus::AwaitingVerification;
         
         Ok(())
     }
@@ -170,22 +317,29 @@ pub mod dex {
         msg!("TEST MODE: Skipping Jupiter CPI");
         let settlement = &mut ctx.accounts.settlement_request;
         require!(settlement.active, ErrorCode::SettlementNotActive);
+        require!(
+            !ctx.accounts.temp_wallet.order_type.waits_for_release() || settlement.triggered,
+            ErrorCode::TriggerNotPassed
+        );
         settlement.active = false;
+        // Whatever already sits in the output account stands in for the swap.
+        settlement.realized_out = ctx.accounts.temp_token_account_out.amount;
+        settlement.fill_status = FillStatus::AwaitingVerification;
         Ok(())
     }
 
     // !!! TEST ONLY: Backdoor to simulate Arcium callback for testing Jupiter CPI integration !!!
+    // Skips every check match_order makes, so it only exists in devnet builds.
+    #[cfg(feature = "devnet")]
     pub fn simulate_match_order(
         ctx: Context<SimulateMatchOrder>,
         amount_in: u64,
-        amount_out_min: u64,
-        token_in: u64,
-        token_out: u64,
+        token_in: Pubkey,
+        token_out: Pubkey,
         nonce: u64,
     ) -> Result<()> {
         let settlement = &mut ctx.accounts.settlement_request;
         settlement.amount_in = amount_in;
-        settlement.min_out = amount_out_min;
         settlement.token_in = token_in;
         settlement.token_out = token_out;
         settlement.nonce = nonce;
@@ -193,13 +347,14 @@ pub mod dex {
         
         emit!(OrderSettledEvent {
             amount_in,
-            min_out: amount_out_min,
             token_in,
             token_out,
             nonce,
         });
         Ok(())
     }
+
+    #[cfg(feature = "devnet")]
     pub fn init_settlement_test(
         ctx: Context<InitSettlementTest>,
         public_nonce: u64,
@@ -211,43 +366,36 @@ pub mod dex {
         Ok(())
     }
 
-    pub fn register_token(ctx: Context<RegisterToken>, id: u64) -> Result<()> {
-        let mapping = &mut ctx.accounts.token_mapping;
-        mapping.mint = ctx.accounts.mint.key();
-        mapping.id = id;
-        msg!("Registered token ID {} for mint {}", id, mapping.mint);
-        Ok(())
-    }
-
     pub fn create_private_swap(
         ctx: Context<CreatePrivateSwap>,
         amount_in: u64,
-        min_out: u64,
         nonce: u64,
         computation_offset: u64,
-        encrypted_amount_in: [u8; 32],
-        encrypted_amount_out_min: [u8; 32],
-        encrypted_token_in: [u8; 32],
-        encrypted_token_out: [u8; 32],
-        encrypted_nonce: [u8; 32],
+        order: EncryptedOrder,
+        order_type: OrderType,
+        price_feed: Pubkey,
+        trigger_above: bool,
     ) -> Result<()> {
+        require!(
+            order_type.names_price_feed() == (price_feed != Pubkey::default()),
+            ErrorCode::InvalidPriceFeed
+        );
+
         let temp = &mut ctx.accounts.temp_wallet;
         temp.user = ctx.accounts.payer.key();
         temp.token_in_mint = ctx.accounts.token_in_mint.key();
         temp.token_out_mint = ctx.accounts.token_out_mint.key();
         temp.amount_in = amount_in;
-        temp.min_out = min_out;
         temp.nonce = nonce;
         temp.active = true;
         temp.is_funded = false;
         temp.bump = ctx.bumps.temp_wallet;
         
         temp.computation_offset = computation_offset;
-        temp.encrypted_amount_in = encrypted_amount_in;
-        temp.encrypted_amount_out_min = encrypted_amount_out_min;
-        temp.encrypted_token_in = encrypted_token_in;
-        temp.encrypted_token_out = encrypted_token_out;
-        temp.encrypted_nonce = encrypted_nonce;
+        temp.set_order(&order);
+        temp.order_type = order_type;
+        temp.price_feed = price_feed;
+        temp.trigger_above = trigger_above;
 
         emit!(TempWalletCreated {
             temp_wallet: temp.key(),
@@ -260,10 +408,21 @@ pub mod dex {
         Ok(())
     }
 
-    pub fn fund_and_place_order(ctx: Context<FundAndPlaceOrder>, computation_offset: u64) -> Result<()> {
+    pub fn fund_and_place_order(
+        ctx: Context<FundAndPlaceOrder>,
+        computation_offset: u64,
+        state_nonce: u128,
+        disclosure_nonce: u128,
+    ) -> Result<()> {
         let temp = &mut ctx.accounts.temp_wallet;
         require!(temp.active, ErrorCode::SettlementNotActive);
         require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require!(temp.order_type != OrderType::Midpoint, ErrorCode::MidpointOrder);
+        require!(
+            temp.access_policy == Pubkey::default() || (temp.access_granted && !temp.access_pending),
+            ErrorCode::AccessNotGranted
+        );
+        require_pool_access(&ctx.accounts.pool_policy, temp, ctx.program_id)?;
 
         let balance = ctx.accounts.temp_token_account.amount;
         require!(balance >= temp.amount_in, ErrorCode::InsufficientFunds);
@@ -276,34 +435,202 @@ pub mod dex {
         settlement.active = false;
         settlement.nonce = temp.nonce;
         settlement.bump = ctx.bumps.settlement_request;
+        let settlement_key = settlement.key();
 
-        let enc_amount_in = temp.encrypted_amount_in;
-        let enc_min_out = temp.encrypted_amount_out_min;
-        let enc_token_in = temp.encrypted_token_in;
-        let enc_token_out = temp.encrypted_token_out;
-        let enc_nonce = temp.encrypted_nonce;
+        let receipt = &mut ctx.accounts.order_receipt;
+        receipt.user = temp.user;
+        receipt.nonce = temp.nonce;
+        receipt.bump = ctx.bumps.order_receipt;
+        let receipt_key = receipt.key();
+        let temp_key = temp.key();
 
-        let args = vec![
-            Argument::EncryptedU64(enc_amount_in),
-            Argument::EncryptedU64(enc_min_out),
-            Argument::EncryptedU64(enc_token_in),
-            Argument::EncryptedU64(enc_token_out),
-            Argument::EncryptedU64(enc_nonce),
-        ];
+        let auditor = auditor_key(&ctx.accounts.audit_config)?;
+        let disclosure = &mut ctx.accounts.order_disclosure;
+        disclosure.nonce = temp.nonce;
+        disclosure.auditor_key = auditor.unwrap_or_default();
+        disclosure.bump = ctx.bumps.order_disclosure;
+        let disclosure_key = disclosure.key();
+
+        // The public half of the order; match_order refuses to settle unless
+        // the ciphertexts agree with it.
+        let deadline_slot = Clock::get()?.slot + MAX_MATCH_LATENCY_SLOTS;
+        let args = match_order_args(
+            temp,
+            deadline_slot,
+            auditor.unwrap_or(temp.encryption_pubkey),
+            disclosure_nonce,
+            state_nonce,
+        );
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchOrderCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: settlement_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: receipt_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: temp_key,
+                    is_writable: false,
+                },
+                CallbackAccount {
+                    pubkey: disclosure_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    /// Withdraws a placed order, including one whose match_order job is
+    /// still in flight. The settlement and receipt are closed and the
+    /// revision bumped so a late callback can't revive them; the deposit is
+    /// then reclaimed with refund_tokens_to_user.
+    pub fn cancel_order(ctx: Context<CancelOrder>) -> Result<()> {
+        let temp = &mut ctx.accounts.temp_wallet;
+        require!(temp.is_funded, ErrorCode::SettlementNotActive);
+
+        temp.is_funded = false;
+        temp.revision = temp.revision.wrapping_add(1);
+
+        emit!(OrderCancelledEvent { nonce: temp.nonce });
+
+        Ok(())
+    }
+
+    /// Replaces a placed order's ciphertexts and re-queues match_order. The
+    /// public deposit, mints and order type stay as they are; only the hidden
+    /// terms change. Not available once the order has started executing.
+    pub fn amend_order(
+        ctx: Context<AmendOrder>,
+        computation_offset: u64,
+        order: EncryptedOrder,
+        state_nonce: u128,
+        disclosure_nonce: u128,
+    ) -> Result<()> {
+        let settlement = &mut ctx.accounts.settlement_request;
+        let temp = &mut ctx.accounts.temp_wallet;
+        require!(temp.is_funded, ErrorCode::SettlementNotActive);
+        require!(
+            temp.order_type != OrderType::Twap
+                && !settlement.partial
+                && settlement.spent_in == 0
+                && settlement.fill_status == FillStatus::Pending,
+            ErrorCode::OrderNotAmendable
+        );
+
+        temp.set_order(&order);
+        temp.computation_offset = computation_offset;
+        temp.revision = temp.revision.wrapping_add(1);
+
+        // Back to the state fund_and_place_order leaves it in until the new
+        // result lands.
+        settlement.active = false;
+        settlement.amount_in = 0;
+        settlement.awaiting_trigger = false;
+        settlement.triggered = false;
+        settlement.slice_amount = 0;
+
+        let settlement_key = settlement.key();
+        let receipt_key = ctx.accounts.order_receipt.key();
+        let temp_key = temp.key();
+
+        // Follow the current audit config rather than the one the order was
+        // first placed under.
+        let auditor = auditor_key(&ctx.accounts.audit_config)?;
+        let disclosure = &mut ctx.accounts.order_disclosure;
+        disclosure.auditor_key = auditor.unwrap_or_default();
+        let disclosure_key = disclosure.key();
+
+        let deadline_slot = Clock::get()?.slot + MAX_MATCH_LATENCY_SLOTS;
+        let args = match_order_args(
+            temp,
+            deadline_slot,
+            auditor.unwrap_or(temp.encryption_pubkey),
+            disclosure_nonce,
+            state_nonce,
+        );
+
+        emit!(OrderAmendedEvent {
+            nonce: temp.nonce,
+            revision: temp.revision,
+        });
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
         queue_computation(
             ctx.accounts,
             computation_offset,
             args,
             None,
-            vec![MatchOrderCallback::callback_ix(&[])],
+            vec![MatchOrderCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: settlement_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: receipt_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: temp_key,
+                    is_writable: false,
+                },
+                CallbackAccount {
+                    pubkey: disclosure_key,
+                    is_writable: true,
+                },
+            ])],
         )?;
 
         Ok(())
     }
 
     pub fn return_tokens_to_user(ctx: Context<ReturnTokensToUser>) -> Result<()> {
+        // Swap output is only released once verify_fill has passed it. A
+        // failed fill is unwound with unwind_failed_fill, unless the user
+        // signs for the output themselves and so accepts it as is.
+        if !ctx.accounts.settlement_request.data_is_empty() {
+            let data = ctx.accounts.settlement_request.try_borrow_data()?;
+            let settlement: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
+            match settlement.fill_status {
+                FillStatus::AwaitingVerification => return err!(ErrorCode::FillNotVerified),
+                FillStatus::Failed => require_keys_eq!(
+                    ctx.accounts.payer.key(),
+                    ctx.accounts.user.key(),
+                    ErrorCode::FillNotVerified
+                ),
+                FillStatus::Pending | FillStatus::Verified | FillStatus::Unwound => {}
+            }
+        }
+
         let amount = ctx.accounts.temp_token_account_out.amount;
+
+        // Mints without a fee schedule pay no fee. Otherwise everything being
+        // returned must have gone through assess_fee first.
+        let fee = if ctx.accounts.fee_schedule.data_is_empty() {
+            0
+        } else {
+            let temp = &ctx.accounts.temp_wallet;
+            require!(!temp.fee_pending && temp.fee_base == amount, ErrorCode::FeeNotAssessed);
+            let data = ctx.accounts.fee_schedule.try_borrow_data()?;
+            let schedule: FeeSchedule = AccountDeserialize::try_deserialize(&mut &data[..])?;
+            require_keys_eq!(
+                ctx.accounts.treasury_token_account.key(),
+                schedule.treasury_token_account,
+                ErrorCode::InvalidTreasury
+            );
+            temp.fee_due.min(amount)
+        };
+
         if amount > 0 {
             let bump = ctx.accounts.temp_wallet.bump;
             let bump_arr = [bump];
@@ -316,18 +643,35 @@ pub mod dex {
             ];
             let signer = &[&seeds[..]];
 
-            token::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.temp_token_account_out.to_account_info(),
-                        to: ctx.accounts.user_token_account_out.to_account_info(),
-                        authority: ctx.accounts.temp_wallet.to_account_info(),
-                    },
-                    signer
-                ),
-                amount
-            )?;
+            if fee > 0 {
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.temp_token_account_out.to_account_info(),
+                            to: ctx.accounts.treasury_token_account.to_account_info(),
+                            authority: ctx.accounts.temp_wallet.to_account_info(),
+                        },
+                        signer
+                    ),
+                    fee
+                )?;
+            }
+
+            if amount > fee {
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.temp_token_account_out.to_account_info(),
+                            to: ctx.accounts.user_token_account_out.to_account_info(),
+                            authority: ctx.accounts.temp_wallet.to_account_info(),
+                        },
+                        signer
+                    ),
+                    amount - fee
+                )?;
+            }
         }
         
         let bump = ctx.accounts.temp_wallet.bump;
@@ -355,193 +699,8035 @@ pub mod dex {
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(id: u64)]
-pub struct RegisterToken<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 32 + 8,
-        seeds = [b"token", id.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub token_mapping: Account<'info, TokenMapping>,
-    /// CHECK: We just store the pubkey, we don't need full Mint struct validation here strictly, but using Account<'info, Mint> is safer if we want to ensure it exists.
-    /// However, to avoid importing spl-token crate if not needed, we can just use UncheckedAccount or check owner.
-    /// Since we imported anchor_spl::token::TokenAccount, let's assume we can import Mint.
-    /// But wait, the user didn't import Mint in line 3, only Token and TokenAccount.
-    /// Let's use UncheckedAccount for flexibility or add Mint to imports.
-    pub mint: UncheckedAccount<'info>, 
-    pub system_program: Program<'info, System>,
-}
+    pub fn init_verify_fill_comp_def(ctx: Context<InitVerifyFillCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(public_nonce: u64)]
-pub struct InitSettlementTest<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1,
-        seeds = [b"settlement", public_nonce.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub settlement_request: Account<'info, SettlementRequest>,
-    pub system_program: Program<'info, System>,
-}
+    pub fn verify_fill(ctx: Context<VerifyFill>, computation_offset: u64) -> Result<()> {
+        let settlement = &ctx.accounts.settlement_request;
+        require!(
+            settlement.fill_status == FillStatus::AwaitingVerification,
+            ErrorCode::FillNotAwaitingVerification
+        );
+        require!(!settlement.partial, ErrorCode::PartialFillOrder);
+        require!(!ctx.accounts.temp_wallet.order_type.worked_in_rounds(), ErrorCode::RoundBasedOrder);
+        let realized_out = settlement.realized_out;
+        let settlement_key = settlement.key();
 
-#[derive(Accounts)]
-#[instruction(amount_in: u64, amount_out_min: u64, token_in: u64, token_out: u64, nonce: u64)]
-pub struct SimulateMatchOrder<'info> {
-    #[account(
-        mut,
-        seeds = [b"settlement", nonce.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub settlement_request: Account<'info, SettlementRequest>,
-    pub payer: Signer<'info>,
-}
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
-#[queue_computation_accounts("match_order", payer)]
-#[derive(Accounts)]
-#[instruction(computation_offset: u64, amount_in: [u8; 32], amount_out_min: [u8; 32], token_in: [u8; 32], token_out: [u8; 32], nonce: [u8; 32], public_nonce: u64)]
-pub struct PlaceOrder<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        init_if_needed,
-        space = 9,
-        payer = payer,
-        seeds = [&SIGN_PDA_SEED],
-        bump,
-        address = derive_sign_pda!(),
-    )]
-    pub sign_pda_account: Account<'info, SignerAccount>,
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1, // Discriminator + fields
-        seeds = [b"settlement", public_nonce.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub settlement_request: Account<'info, SettlementRequest>,
-    #[account(
-        address = derive_mxe_pda!()
-    )]
-    pub mxe_account: Account<'info, MXEAccount>,
-    #[account(
-        mut,
-        address = derive_mempool_pda!()
-    )]
-    /// CHECK: mempool_account, checked by the arcium program.
-    pub mempool_account: UncheckedAccount<'info>,
-    #[account(
-        mut,
-        address = derive_execpool_pda!()
-    )]
-    /// CHECK: executing_pool, checked by the arcium program.
-    pub executing_pool: UncheckedAccount<'info>,
-    #[account(
-        mut,
-        address = derive_comp_pda!(computation_offset)
-    )]
-    /// CHECK: computation_account, checked by the arcium program.
-    pub computation_account: UncheckedAccount<'info>,
-    #[account(
-        address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER)
-    )]
-    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
-    #[account(
+        let mut args = swap_order_args(&ctx.accounts.temp_wallet);
+        args.push(Argument::PlaintextU64(realized_out));
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![VerifyFillCallback::callback_ix(&[CallbackAccount {
+                pubkey: settlement_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "verify_fill", auto_serialize = false)]
+    pub fn verify_fill_callback(
+        ctx: Context<VerifyFillCallback>,
+        output: ComputationOutputs<FillCheck>,
+    ) -> Result<()> {
+        let o = match output {
+            ComputationOutputs::Success(o) => o,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let (pda, _bump) = Pubkey::find_program_address(
+            &[b"settlement", o.nonce.to_le_bytes().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.settlement_request.key(), pda, ErrorCode::InvalidSettlementPDA);
+
+        let mut data = ctx.accounts.settlement_request.try_borrow_mut_data()?;
+        let mut settlement: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
+
+        // A failed fill holds its output back from return_tokens_to_user.
+        // The user either swaps it back with unwind_failed_fill and takes
+        // everything through refund_tokens_to_user, or signs
+        // return_tokens_to_user to accept the output anyway.
+        settlement.fill_status = if o.passed {
+            FillStatus::Verified
+        } else {
+            FillStatus::Failed
+        };
+
+        settlement.try_serialize(&mut *data)?;
+
+        emit!(FillCheckedEvent {
+            nonce: o.nonce,
+            passed: o.passed,
+        });
+
+        Ok(())
+    }
+
+    /// Swaps the output of a failed fill back into token_in so the whole
+    /// deposit can be reclaimed with refund_tokens_to_user. Signed by the
+    /// user, who also picks the route.
+    pub fn unwind_failed_fill(ctx: Context<UnwindFailedFill>, data: Vec<u8>) -> Result<()> {
+        let settlement = &mut ctx.accounts.settlement_request;
+        require!(settlement.fill_status == FillStatus::Failed, ErrorCode::FillNotFailed);
+        settlement.fill_status = FillStatus::Unwound;
+
+        let out_before = ctx.accounts.temp_token_account_out.amount;
+        invoke_jupiter(
+            ctx.accounts.jupiter_program.key(),
+            ctx.remaining_accounts,
+            data,
+            &ctx.accounts.temp_wallet,
+        )?;
+
+        // The route may only sell the fill's output, never pull more input.
+        ctx.accounts.temp_token_account_out.reload()?;
+        require!(ctx.accounts.temp_token_account_out.amount <= out_before, ErrorCode::InvalidUnwindRoute);
+
+        emit!(FillUnwoundEvent {
+            nonce: settlement.nonce,
+            unwound_out: out_before - ctx.accounts.temp_token_account_out.amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_cross_orders_comp_def(ctx: Context<InitCrossOrdersCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn fund_and_cross_orders(ctx: Context<FundAndCrossOrders>, computation_offset: u64) -> Result<()> {
+        let temp_a = &mut ctx.accounts.temp_wallet_a;
+        let temp_b = &mut ctx.accounts.temp_wallet_b;
+        require!(temp_a.active && temp_b.active, ErrorCode::SettlementNotActive);
+        require!(!temp_a.is_funded && !temp_b.is_funded, ErrorCode::AlreadyFunded);
+        // The mints are public on the TempWallet, so refuse obvious mismatches
+        // before paying for an MPC round.
+        require_keys_eq!(temp_a.token_in_mint, temp_b.token_out_mint, ErrorCode::MintMismatch);
+        require_keys_eq!(temp_a.token_out_mint, temp_b.token_in_mint, ErrorCode::MintMismatch);
+        require_pool_access(&ctx.accounts.pool_policy, temp_a, ctx.program_id)?;
+        require_pool_access(&ctx.accounts.pool_policy, temp_b, ctx.program_id)?;
+
+        require!(ctx.accounts.temp_token_account_a.amount >= temp_a.amount_in, ErrorCode::InsufficientFunds);
+        require!(ctx.accounts.temp_token_account_b.amount >= temp_b.amount_in, ErrorCode::InsufficientFunds);
+
+        // Both deposits stay put until the result lands; one that hasn't by
+        // the deadline is dropped and the deposits can be refunded.
+        let deadline_slot = Clock::get()?.slot + MAX_MATCH_LATENCY_SLOTS;
+        temp_a.is_funded = true;
+        temp_b.is_funded = true;
+        temp_a.cross_status = CrossStatus::Pending;
+        temp_b.cross_status = CrossStatus::Pending;
+        temp_a.cross_deadline_slot = deadline_slot;
+        temp_b.cross_deadline_slot = deadline_slot;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let cross = &mut ctx.accounts.cross_settlement;
+        cross.temp_wallet_a = temp_a.key();
+        cross.temp_wallet_b = temp_b.key();
+        cross.nonce_a = temp_a.nonce;
+        cross.nonce_b = temp_b.nonce;
+        cross.active = false;
+        cross.bump = ctx.bumps.cross_settlement;
+
+        let cross_key = cross.key();
+
+        let mut args = swap_order_args(temp_a);
+        args.extend(swap_order_args(temp_b));
+        args.push(Argument::PlaintextU64(temp_a.amount_in));
+        args.push(Argument::PlaintextU64(temp_b.amount_in));
+
+        let callback_accounts: Vec<CallbackAccount> = [cross_key, temp_a.key(), temp_b.key()]
+            .into_iter()
+            .map(|pubkey| CallbackAccount { pubkey, is_writable: true })
+            .collect();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CrossOrdersCallback::callback_ix(&callback_accounts)],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cross_orders", auto_serialize = false)]
+    pub fn cross_orders_callback(
+        ctx: Context<CrossOrdersCallback>,
+        output: ComputationOutputs<CrossResult>,
+    ) -> Result<()> {
+        // A failed or late cross resolves as not crossed; past the deadline
+        // either side may already have refunded its deposit.
+        let now = Clock::get()?.slot;
+        let o = match output {
+            ComputationOutputs::Success(o) if now <= ctx.accounts.temp_wallet_a.cross_deadline_slot => o,
+            _ => CrossResult {
+                crossed: false,
+                fill_a: 0,
+                fill_b: 0,
+                nonce_a: ctx.accounts.temp_wallet_a.nonce,
+                nonce_b: ctx.accounts.temp_wallet_b.nonce,
+            },
+        };
+
+        let (pda, _bump) = Pubkey::find_program_address(
+            &[b"cross", o.nonce_a.to_le_bytes().as_ref(), o.nonce_b.to_le_bytes().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.cross_settlement.key(), pda, ErrorCode::InvalidSettlementPDA);
+
+        let mut data = ctx.accounts.cross_settlement.try_borrow_mut_data()?;
+        let mut cross: CrossSettlement = AccountDeserialize::try_deserialize(&mut &data[..])?;
+
+        cross.fill_a = o.fill_a;
+        cross.fill_b = o.fill_b;
+        cross.active = o.crossed;
+
+        cross.try_serialize(&mut *data)?;
+
+        emit!(OrdersCrossedEvent {
+            nonce_a: o.nonce_a,
+            nonce_b: o.nonce_b,
+            crossed: o.crossed,
+            fill_a: o.fill_a,
+            fill_b: o.fill_b,
+        });
+
+        // A crossed pair stays committed until settle_crossed_orders.
+        let status = if o.crossed { CrossStatus::Crossed } else { CrossStatus::Idle };
+        ctx.accounts.temp_wallet_a.cross_status = status;
+        ctx.accounts.temp_wallet_b.cross_status = status;
+
+        Ok(())
+    }
+
+    pub fn init_match_order_mid_comp_def(ctx: Context<InitMatchOrderMidCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Crosses two Midpoint orders at the mid of the feed they both named.
+    /// A must be the order selling the feed's base asset. The result lands in
+    /// the same CrossSettlement that settle_crossed_orders pays out.
+    pub fn fund_and_cross_at_mid(ctx: Context<FundAndCrossAtMid>, computation_offset: u64) -> Result<()> {
+        let temp_a = &mut ctx.accounts.temp_wallet_a;
+        let temp_b = &mut ctx.accounts.temp_wallet_b;
+        require!(temp_a.active && temp_b.active, ErrorCode::SettlementNotActive);
+        require!(!temp_a.is_funded && !temp_b.is_funded, ErrorCode::AlreadyFunded);
+        require!(
+            temp_a.order_type == OrderType::Midpoint && temp_b.order_type == OrderType::Midpoint,
+            ErrorCode::NotMidpointOrder
+        );
+        require_keys_eq!(temp_a.price_feed, temp_b.price_feed, ErrorCode::InvalidPriceFeed);
+        require!(temp_a.trigger_above && !temp_b.trigger_above, ErrorCode::MidpointSideMismatch);
+        require_keys_eq!(temp_a.token_in_mint, temp_b.token_out_mint, ErrorCode::MintMismatch);
+        require_keys_eq!(temp_a.token_out_mint, temp_b.token_in_mint, ErrorCode::MintMismatch);
+        require_pool_access(&ctx.accounts.pool_policy, temp_a, ctx.program_id)?;
+        require_pool_access(&ctx.accounts.pool_policy, temp_b, ctx.program_id)?;
+
+        require!(ctx.accounts.temp_token_account_a.amount >= temp_a.amount_in, ErrorCode::InsufficientFunds);
+        require!(ctx.accounts.temp_token_account_b.amount >= temp_b.amount_in, ErrorCode::InsufficientFunds);
+
+        let mid_price = read_pyth_mid_price(
+            &ctx.accounts.price_feed,
+            Clock::get()?.slot,
+            ctx.accounts.base_mint.decimals,
+            ctx.accounts.quote_mint.decimals,
+        )?;
+
+        // Same hold as fund_and_cross_orders.
+        let deadline_slot = Clock::get()?.slot + MAX_MATCH_LATENCY_SLOTS;
+        temp_a.is_funded = true;
+        temp_b.is_funded = true;
+        temp_a.cross_status = CrossStatus::Pending;
+        temp_b.cross_status = CrossStatus::Pending;
+        temp_a.cross_deadline_slot = deadline_slot;
+        temp_b.cross_deadline_slot = deadline_slot;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let cross = &mut ctx.accounts.cross_settlement;
+        cross.temp_wallet_a = temp_a.key();
+        cross.temp_wallet_b = temp_b.key();
+        cross.nonce_a = temp_a.nonce;
+        cross.nonce_b = temp_b.nonce;
+        cross.active = false;
+        cross.bump = ctx.bumps.cross_settlement;
+
+        let cross_key = cross.key();
+
+        let mut args = swap_order_args(temp_a);
+        args.extend(swap_order_args(temp_b));
+        args.push(Argument::PlaintextU64(temp_a.amount_in));
+        args.push(Argument::PlaintextU64(temp_b.amount_in));
+        args.push(Argument::PlaintextU128(mid_price));
+
+        let callback_accounts: Vec<CallbackAccount> = [cross_key, temp_a.key(), temp_b.key()]
+            .into_iter()
+            .map(|pubkey| CallbackAccount { pubkey, is_writable: true })
+            .collect();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchOrderMidCallback::callback_ix(&callback_accounts)],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_order_mid", auto_serialize = false)]
+    pub fn match_order_mid_callback(
+        ctx: Context<MatchOrderMidCallback>,
+        output: ComputationOutputs<CrossResult>,
+    ) -> Result<()> {
+        // Resolved like cross_orders_callback.
+        let now = Clock::get()?.slot;
+        let o = match output {
+            ComputationOutputs::Success(o) if now <= ctx.accounts.temp_wallet_a.cross_deadline_slot => o,
+            _ => CrossResult {
+                crossed: false,
+                fill_a: 0,
+                fill_b: 0,
+                nonce_a: ctx.accounts.temp_wallet_a.nonce,
+                nonce_b: ctx.accounts.temp_wallet_b.nonce,
+            },
+        };
+
+        let (pda, _bump) = Pubkey::find_program_address(
+            &[b"cross", o.nonce_a.to_le_bytes().as_ref(), o.nonce_b.to_le_bytes().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.cross_settlement.key(), pda, ErrorCode::InvalidSettlementPDA);
+
+        let mut data = ctx.accounts.cross_settlement.try_borrow_mut_data()?;
+        let mut cross: CrossSettlement = AccountDeserialize::try_deserialize(&mut &data[..])?;
+
+        cross.fill_a = o.fill_a;
+        cross.fill_b = o.fill_b;
+        cross.active = o.crossed;
+
+        cross.try_serialize(&mut *data)?;
+
+        emit!(OrdersCrossedEvent {
+            nonce_a: o.nonce_a,
+            nonce_b: o.nonce_b,
+            crossed: o.crossed,
+            fill_a: o.fill_a,
+            fill_b: o.fill_b,
+        });
+
+        let status = if o.crossed { CrossStatus::Crossed } else { CrossStatus::Idle };
+        ctx.accounts.temp_wallet_a.cross_status = status;
+        ctx.accounts.temp_wallet_b.cross_status = status;
+
+        Ok(())
+    }
+
+    pub fn settle_crossed_orders(ctx: Context<SettleCrossedOrders>) -> Result<()> {
+        let cross = &mut ctx.accounts.cross_settlement;
+        require!(cross.active, ErrorCode::SettlementNotActive);
+        cross.active = false;
+        let fill_a = cross.fill_a;
+        let fill_b = cross.fill_b;
+        ctx.accounts.temp_wallet_a.cross_status = CrossStatus::Idle;
+        ctx.accounts.temp_wallet_b.cross_status = CrossStatus::Idle;
+
+        // A's token_in goes straight to B's token_out account, signed by A.
+        let bump_a = [ctx.accounts.temp_wallet_a.bump];
+        let nonce_a = ctx.accounts.temp_wallet_a.nonce.to_le_bytes();
+        let seeds_a = &[
+            b"temp_wallet",
+            ctx.accounts.temp_wallet_a.user.as_ref(),
+            nonce_a.as_ref(),
+            &bump_a[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account_a_in.to_account_info(),
+                    to: ctx.accounts.temp_token_account_b_out.to_account_info(),
+                    authority: ctx.accounts.temp_wallet_a.to_account_info(),
+                },
+                &[&seeds_a[..]],
+            ),
+            fill_a,
+        )?;
+
+        // And B's token_in to A's token_out account, signed by B.
+        let bump_b = [ctx.accounts.temp_wallet_b.bump];
+        let nonce_b = ctx.accounts.temp_wallet_b.nonce.to_le_bytes();
+        let seeds_b = &[
+            b"temp_wallet",
+            ctx.accounts.temp_wallet_b.user.as_ref(),
+            nonce_b.as_ref(),
+            &bump_b[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account_b_in.to_account_info(),
+                    to: ctx.accounts.temp_token_account_a_out.to_account_info(),
+                    authority: ctx.accounts.temp_wallet_b.to_account_info(),
+                },
+                &[&seeds_b[..]],
+            ),
+            fill_b,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn refund_tokens_to_user(ctx: Context<RefundTokensToUser>) -> Result<()> {
+        let temp = &ctx.accounts.temp_wallet;
+        require!(!temp.access_pending && !temp.fee_pending, ErrorCode::OrderStillLive);
+        // A queued cross holds the deposit until MPC answers or its deadline
+        // passes, and a crossed one until settle_crossed_orders.
+        match temp.cross_status {
+            CrossStatus::Idle => {}
+            CrossStatus::Pending => {
+                require!(Clock::get()?.slot > temp.cross_deadline_slot, ErrorCode::OrderStillLive);
+            }
+            CrossStatus::Crossed => return Err(ErrorCode::OrderStillLive.into()),
+        }
+
+        // A funded order keeps its deposit until it can no longer draw on
+        // it: cancelled (which closes the SettlementRequest), done with its
+        // last fill, or an RFQ that resolved unfilled. Batch and book orders
+        // hold their deposit in the vault, so nothing here is claimed.
+        if temp.is_funded {
+            if !ctx.accounts.settlement_request.data_is_empty() {
+                let data = ctx.accounts.settlement_request.try_borrow_data()?;
+                let settlement: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
+                let finished = matches!(
+                    settlement.fill_status,
+                    FillStatus::Verified | FillStatus::Failed | FillStatus::Unwound
+                );
+                require!(finished && !settlement.active, ErrorCode::OrderStillLive);
+            }
+            if !ctx.accounts.rfq.data_is_empty() {
+                let data = ctx.accounts.rfq.try_borrow_data()?;
+                let rfq: Rfq = AccountDeserialize::try_deserialize(&mut &data[..])?;
+                require!(rfq.resolved && !rfq.filled, ErrorCode::OrderStillLive);
+            }
+        }
+
+        let amount = ctx.accounts.temp_token_account_in.amount;
+
+        let bump = ctx.accounts.temp_wallet.bump;
+        let bump_arr = [bump];
+        let nonce_bytes = ctx.accounts.temp_wallet.nonce.to_le_bytes();
+        let seeds = &[
+            b"temp_wallet",
+            ctx.accounts.temp_wallet.user.as_ref(),
+            nonce_bytes.as_ref(),
+            &bump_arr[..]
+        ];
+        let signer = &[&seeds[..]];
+
+        if amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.temp_token_account_in.to_account_info(),
+                        to: ctx.accounts.user_token_account_in.to_account_info(),
+                        authority: ctx.accounts.temp_wallet.to_account_info(),
+                    },
+                    signer
+                ),
+                amount
+            )?;
+        }
+
+        token::close_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: ctx.accounts.temp_token_account_in.to_account_info(),
+                    destination: ctx.accounts.user.to_account_info(),
+                    authority: ctx.accounts.temp_wallet.to_account_info(),
+                },
+                signer
+            )
+        )?;
+
+        Ok(())
+    }
+
+    pub fn init_open_batch_comp_def(ctx: Context<InitOpenBatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_join_batch_comp_def(ctx: Context<InitJoinBatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_clear_batch_comp_def(ctx: Context<InitClearBatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn open_batch(
+        ctx: Context<OpenBatch>,
+        computation_offset: u64,
+        epoch: u64,
+        state_nonce: u128,
+    ) -> Result<()> {
+        require!(epoch == Clock::get()?.slot / BATCH_EPOCH_SLOTS, ErrorCode::BatchEpochMismatch);
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let batch = &mut ctx.accounts.batch;
+        batch.base_mint = ctx.accounts.base_mint.key();
+        batch.quote_mint = ctx.accounts.quote_mint.key();
+        batch.epoch = epoch;
+        batch.count = 0;
+        batch.pending = true;
+        batch.pending_since = Clock::get()?.slot;
+        batch.cleared = false;
+        batch.aborted = false;
+        batch.bump = ctx.bumps.batch;
+        let batch_key = batch.key();
+
+        let args = vec![Argument::PlaintextU128(state_nonce)];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![OpenBatchCallback::callback_ix(&[CallbackAccount {
+                pubkey: batch_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "open_batch", auto_serialize = false)]
+    pub fn open_batch_callback(
+        ctx: Context<OpenBatchCallback>,
+        output: ComputationOutputs<EncryptedBatchBook>,
+    ) -> Result<()> {
+        let batch = &mut ctx.accounts.batch;
+        // Too late; abort_stuck_batch already gave up on this computation.
+        if batch.aborted {
+            return Ok(());
+        }
+        let book = match output {
+            ComputationOutputs::Success(book) => book,
+            _ => {
+                abort_batch(batch);
+                return Ok(());
+            }
+        };
+
+        batch.encrypted_book = book.ciphertexts;
+        batch.state_nonce = book.nonce;
+        batch.pending = false;
+
+        Ok(())
+    }
+
+    pub fn join_batch(ctx: Context<JoinBatch>, computation_offset: u64) -> Result<()> {
+        let temp = &mut ctx.accounts.temp_wallet;
+        let batch = &mut ctx.accounts.batch;
+        require!(temp.active, ErrorCode::SettlementNotActive);
+        require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require_pool_access(&ctx.accounts.pool_policy, temp, ctx.program_id)?;
+        require!(!batch.pending, ErrorCode::BatchBusy);
+        require!(!batch.cleared && !batch.aborted, ErrorCode::BatchClosed);
+        require!(batch.epoch == Clock::get()?.slot / BATCH_EPOCH_SLOTS, ErrorCode::BatchClosed);
+        require!((batch.count as usize) < BATCH_SIZE, ErrorCode::BatchFull);
+
+        let same_side = temp.token_in_mint == batch.base_mint && temp.token_out_mint == batch.quote_mint;
+        let other_side = temp.token_in_mint == batch.quote_mint && temp.token_out_mint == batch.base_mint;
+        require!(same_side || other_side, ErrorCode::MintMismatch);
+
+        let amount = temp.amount_in;
+        require!(ctx.accounts.temp_token_account.amount >= amount, ErrorCode::InsufficientFunds);
+
+        temp.is_funded = true;
+
+        // Escrow the deposit in the batch vault; whatever the auction doesn't
+        // consume is refunded at settlement.
+        let bump_arr = [temp.bump];
+        let nonce_bytes = temp.nonce.to_le_bytes();
+        let seeds = &[
+            b"temp_wallet",
+            temp.user.as_ref(),
+            nonce_bytes.as_ref(),
+            &bump_arr[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account.to_account_info(),
+                    to: ctx.accounts.batch_vault_in.to_account_info(),
+                    authority: temp.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+        )?;
+
+        let slot = batch.count;
+        batch.temp_wallets[slot as usize] = temp.key();
+        batch.count += 1;
+        batch.pending = true;
+        batch.pending_since = Clock::get()?.slot;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let batch_key = batch.key();
+        let [base_lo, base_hi] = mint_limbs(&batch.base_mint);
+        let [quote_lo, quote_hi] = mint_limbs(&batch.quote_mint);
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::PlaintextU128(batch.state_nonce),
+            Argument::Account(batch_key, 8, 32 * BATCH_BOOK_CIPHERTEXTS as u32),
+            Argument::PlaintextU8(slot),
+            // What was just escrowed; join_batch empties the slot unless the
+            // encrypted amount_in matches it.
+            Argument::PlaintextU64(amount),
+            Argument::PlaintextU128(base_lo),
+            Argument::PlaintextU128(base_hi),
+            Argument::PlaintextU128(quote_lo),
+            Argument::PlaintextU128(quote_hi),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![JoinBatchCallback::callback_ix(&[CallbackAccount {
+                pubkey: batch_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "join_batch", auto_serialize = false)]
+    pub fn join_batch_callback(
+        ctx: Context<JoinBatchCallback>,
+        output: ComputationOutputs<EncryptedBatchBook>,
+    ) -> Result<()> {
+        let batch = &mut ctx.accounts.batch;
+        // Too late; abort_stuck_batch already gave up on this computation.
+        if batch.aborted {
+            return Ok(());
+        }
+        let book = match output {
+            ComputationOutputs::Success(book) => book,
+            _ => {
+                abort_batch(batch);
+                return Ok(());
+            }
+        };
+
+        batch.encrypted_book = book.ciphertexts;
+        batch.state_nonce = book.nonce;
+        batch.pending = false;
+
+        emit!(BatchJoinedEvent {
+            batch: batch.key(),
+            count: batch.count,
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_batch(ctx: Context<ClearBatch>, computation_offset: u64) -> Result<()> {
+        let batch = &mut ctx.accounts.batch;
+        require!(!batch.pending, ErrorCode::BatchBusy);
+        require!(!batch.cleared && !batch.aborted, ErrorCode::BatchClosed);
+        require!(batch.count > 0, ErrorCode::BatchNotReady);
+        // A full batch clears immediately, otherwise wait for the epoch to end.
+        let now = Clock::get()?.slot;
+        let epoch_over = now / BATCH_EPOCH_SLOTS > batch.epoch;
+        require!(batch.count as usize == BATCH_SIZE || epoch_over, ErrorCode::BatchNotReady);
+
+        batch.pending = true;
+        batch.pending_since = now;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let batch_key = batch.key();
+        let args = vec![
+            Argument::PlaintextU128(batch.state_nonce),
+            Argument::Account(batch_key, 8, 32 * BATCH_BOOK_CIPHERTEXTS as u32),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ClearBatchCallback::callback_ix(&[CallbackAccount {
+                pubkey: batch_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "clear_batch", auto_serialize = false)]
+    pub fn clear_batch_callback(
+        ctx: Context<ClearBatchCallback>,
+        output: ComputationOutputs<BatchClearing>,
+    ) -> Result<()> {
+        let batch = &mut ctx.accounts.batch;
+        // Too late; abort_stuck_batch already gave up on this computation.
+        if batch.aborted {
+            return Ok(());
+        }
+        let clearing = match output {
+            ComputationOutputs::Success(clearing) => clearing,
+            _ => {
+                abort_batch(batch);
+                return Ok(());
+            }
+        };
+
+        batch.clearing_price = clearing.price;
+        for (i, fill) in clearing.fills.iter().enumerate().take(batch.count as usize) {
+            batch.fills_in[i] = fill.filled_in;
+            batch.amounts_out[i] = fill.amount_out;
+        }
+        batch.cleared = true;
+        batch.pending = false;
+
+        emit!(BatchClearedEvent {
+            batch: batch.key(),
+            clearing_price: clearing.price,
+            count: batch.count,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless. Gives up on a batch whose open, join or clear never came
+    /// back; settle_batch_order then refunds every deposit in it.
+    pub fn abort_stuck_batch(ctx: Context<AbortStuckBatch>) -> Result<()> {
+        let batch = &mut ctx.accounts.batch;
+        require!(batch.pending, ErrorCode::BatchNotStuck);
+        require!(
+            Clock::get()?.slot > batch.pending_since + BATCH_PENDING_TIMEOUT_SLOTS,
+            ErrorCode::BatchNotStuck
+        );
+
+        abort_batch(batch);
+
+        Ok(())
+    }
+
+    pub fn settle_batch_order(ctx: Context<SettleBatchOrder>, slot: u8) -> Result<()> {
+        let batch = &ctx.accounts.batch;
+        require!(batch.cleared || batch.aborted, ErrorCode::BatchNotCleared);
+        require!(slot < batch.count, ErrorCode::InvalidBatchSlot);
+        require!(!batch.settled[slot as usize], ErrorCode::AlreadySettled);
+        require_keys_eq!(batch.temp_wallets[slot as usize], ctx.accounts.temp_wallet.key(), ErrorCode::InvalidBatchSlot);
+
+        // An aborted batch never cleared, so every deposit goes back whole.
+        let (filled_in, amount_out) = if batch.aborted {
+            (0, 0)
+        } else {
+            (batch.fills_in[slot as usize], batch.amounts_out[slot as usize])
+        };
+        let deposit = ctx.accounts.temp_wallet.amount_in;
+        require!(filled_in <= deposit, ErrorCode::FillExceedsDeposit);
+        let refund = deposit - filled_in;
+        // The vaults only ever hold what was escrowed, so a payout they can't
+        // cover means the clearing is wrong.
+        require!(amount_out <= ctx.accounts.batch_vault_out.amount, ErrorCode::FillExceedsDeposit);
+        require!(refund <= ctx.accounts.batch_vault_in.amount, ErrorCode::FillExceedsDeposit);
+
+        let epoch_bytes = batch.epoch.to_le_bytes();
+        let bump_arr = [batch.bump];
+        let seeds = &[
+            b"batch",
+            batch.base_mint.as_ref(),
+            batch.quote_mint.as_ref(),
+            epoch_bytes.as_ref(),
+            &bump_arr[..],
+        ];
+        let signer = &[&seeds[..]];
+
+        if amount_out > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.batch_vault_out.to_account_info(),
+                        to: ctx.accounts.temp_token_account_out.to_account_info(),
+                        authority: ctx.accounts.batch.to_account_info(),
+                    },
+                    signer,
+                ),
+                amount_out,
+            )?;
+        }
+
+        if refund > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.batch_vault_in.to_account_info(),
+                        to: ctx.accounts.temp_token_account_in.to_account_info(),
+                        authority: ctx.accounts.batch.to_account_info(),
+                    },
+                    signer,
+                ),
+                refund,
+            )?;
+        }
+
+        ctx.accounts.batch.settled[slot as usize] = true;
+
+        Ok(())
+    }
+
+    pub fn init_shuffle_sequence_comp_def(ctx: Context<InitShuffleSequenceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn open_sequence(ctx: Context<OpenSequence>, epoch: u64) -> Result<()> {
+        require!(epoch == Clock::get()?.slot / BATCH_EPOCH_SLOTS, ErrorCode::BatchEpochMismatch);
+
+        let round = &mut ctx.accounts.sequence;
+        round.base_mint = ctx.accounts.base_mint.key();
+        round.quote_mint = ctx.accounts.quote_mint.key();
+        round.epoch = epoch;
+        round.count = 0;
+        round.pending = false;
+        round.shuffled = false;
+        round.cursor = 0;
+        round.bump = ctx.bumps.sequence;
+
+        Ok(())
+    }
+
+    // Takes a matched market order off the keeper's hands until the round is
+    // shuffled and release_sequenced reaches it.
+    pub fn join_sequence(ctx: Context<JoinSequence>) -> Result<()> {
+        let temp = &ctx.accounts.temp_wallet;
+        let settlement = &mut ctx.accounts.settlement_request;
+        let round = &mut ctx.accounts.sequence;
+        require!(!round.pending && !round.shuffled, ErrorCode::SequenceClosed);
+        require!(round.epoch == Clock::get()?.slot / BATCH_EPOCH_SLOTS, ErrorCode::SequenceClosed);
+        require!((round.count as usize) < BATCH_SIZE, ErrorCode::SequenceFull);
+
+        let same_side = temp.token_in_mint == round.base_mint && temp.token_out_mint == round.quote_mint;
+        let other_side = temp.token_in_mint == round.quote_mint && temp.token_out_mint == round.base_mint;
+        require!(same_side || other_side, ErrorCode::MintMismatch);
+
+        require!(
+            temp.order_type == OrderType::Market
+                && settlement.active
+                && !settlement.partial
+                && settlement.fill_status == FillStatus::Pending,
+            ErrorCode::NotSequenceable
+        );
+
+        settlement.active = false;
+
+        let slot = round.count;
+        round.settlements[slot as usize] = settlement.key();
+        round.count += 1;
+
+        emit!(SequenceJoinedEvent {
+            sequence: round.key(),
+            count: round.count,
+        });
+
+        Ok(())
+    }
+
+    pub fn shuffle_sequence(ctx: Context<ShuffleSequence>, computation_offset: u64) -> Result<()> {
+        let round = &mut ctx.accounts.sequence;
+        require!(!round.pending && !round.shuffled, ErrorCode::SequenceClosed);
+        require!(round.count > 0, ErrorCode::SequenceNotReady);
+        // Like a batch, a full round shuffles immediately, otherwise it waits
+        // for the epoch to end.
+        let epoch_over = Clock::get()?.slot / BATCH_EPOCH_SLOTS > round.epoch;
+        require!(round.count as usize == BATCH_SIZE || epoch_over, ErrorCode::SequenceNotReady);
+
+        round.pending = true;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let round_key = round.key();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            vec![],
+            None,
+            vec![ShuffleSequenceCallback::callback_ix(&[CallbackAccount {
+                pubkey: round_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "shuffle_sequence", auto_serialize = false)]
+    pub fn shuffle_sequence_callback(
+        ctx: Context<ShuffleSequenceCallback>,
+        output: ComputationOutputs<ExecutionOrder>,
+    ) -> Result<()> {
+        let order = match output {
+            ComputationOutputs::Success(order) => order,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let round = &mut ctx.accounts.sequence;
+        let count = round.count;
+        let mut position = 0;
+        for &slot in order.slots.iter().filter(|&&slot| slot < count) {
+            round.execution_order[position] = slot;
+            position += 1;
+        }
+        round.shuffled = true;
+        round.pending = false;
+        round.cursor = 0;
+        round.turn_started_slot = Clock::get()?.slot;
+
+        emit!(SequenceShuffledEvent {
+            sequence: round.key(),
+            execution_order: round.execution_order,
+            count: round.count,
+        });
+
+        Ok(())
+    }
+
+    // Permissionless crank: reactivates the next settlement in the shuffled
+    // order once the previous one has executed, been cancelled, or let its
+    // turn run out. `previous_settlement` is ignored for the first release.
+    pub fn release_sequenced(ctx: Context<ReleaseSequenced>) -> Result<()> {
+        let round = &mut ctx.accounts.sequence;
+        require!(round.shuffled, ErrorCode::SequenceNotShuffled);
+        require!(round.cursor < round.count, ErrorCode::SequenceDone);
+
+        let now = Clock::get()?.slot;
+        if round.cursor > 0 {
+            let prev_slot = round.execution_order[round.cursor as usize - 1] as usize;
+            let previous = &ctx.accounts.previous_settlement;
+            require_keys_eq!(previous.key(), round.settlements[prev_slot], ErrorCode::InvalidSequenceAccount);
+
+            // cancel_order closes the request outright.
+            let data = previous.try_borrow_data()?;
+            let finished = data.is_empty() || {
+                let prev: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
+                !prev.active
+            };
+            require!(
+                finished || now > round.turn_started_slot + SEQUENCE_TURN_SLOTS,
+                ErrorCode::SequenceTurnPending
+            );
+        }
+
+        let slot = round.execution_order[round.cursor as usize] as usize;
+        let settlement_info = &ctx.accounts.settlement_request;
+        require_keys_eq!(settlement_info.key(), round.settlements[slot], ErrorCode::InvalidSequenceAccount);
+
+        let mut data = settlement_info.try_borrow_mut_data()?;
+        if !data.is_empty() {
+            let mut settlement: SettlementRequest = AccountDeserialize::try_deserialize(&mut &data[..])?;
+            settlement.active = true;
+            settlement.try_serialize(&mut *data)?;
+        }
+
+        round.cursor += 1;
+        round.turn_started_slot = now;
+
+        emit!(SequenceReleasedEvent {
+            sequence: round.key(),
+            settlement: settlement_info.key(),
+            position: round.cursor,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_open_pair_stats_comp_def(ctx: Context<InitOpenPairStatsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_record_pair_stats_comp_def(ctx: Context<InitRecordPairStatsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_pair_stats_comp_def(ctx: Context<InitRevealPairStatsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn open_pair_stats(
+        ctx: Context<OpenPairStats>,
+        computation_offset: u64,
+        epoch: u64,
+        state_nonce: u128,
+    ) -> Result<()> {
+        require!(epoch == Clock::get()?.slot / STATS_EPOCH_SLOTS, ErrorCode::StatsEpochMismatch);
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let stats = &mut ctx.accounts.pair_stats;
+        stats.base_mint = ctx.accounts.base_mint.key();
+        stats.quote_mint = ctx.accounts.quote_mint.key();
+        stats.epoch = epoch;
+        stats.pending = true;
+        stats.revealed = false;
+        stats.bump = ctx.bumps.pair_stats;
+        let stats_key = stats.key();
+
+        let args = vec![Argument::PlaintextU128(state_nonce)];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![OpenPairStatsCallback::callback_ix(&[CallbackAccount {
+                pubkey: stats_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "open_pair_stats", auto_serialize = false)]
+    pub fn open_pair_stats_callback(
+        ctx: Context<OpenPairStatsCallback>,
+        output: ComputationOutputs<EncryptedPairStats>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let stats = &mut ctx.accounts.pair_stats;
+        stats.encrypted_stats = state.ciphertexts;
+        stats.state_nonce = state.nonce;
+        stats.pending = false;
+
+        Ok(())
+    }
+
+    // Permissionless crank that folds one verified fill into the current
+    // epoch's totals, straight from the order's ciphertexts. Each settlement
+    // is counted once; TWAP slices and partial rounds are left out because
+    // their encrypted size is the whole order, not what traded.
+    pub fn record_pair_stats(ctx: Context<RecordPairStats>, computation_offset: u64) -> Result<()> {
+        let temp = &ctx.accounts.temp_wallet;
+        let settlement = &mut ctx.accounts.settlement_request;
+        let stats = &mut ctx.accounts.pair_stats;
+        require!(!stats.pending, ErrorCode::StatsBusy);
+        require!(!stats.revealed, ErrorCode::StatsClosed);
+        require!(stats.epoch == Clock::get()?.slot / STATS_EPOCH_SLOTS, ErrorCode::StatsClosed);
+        require!(
+            settlement.fill_status == FillStatus::Verified
+                && !settlement.partial
+                && settlement.slice_amount == 0,
+            ErrorCode::FillNotRecordable
+        );
+        require!(!settlement.stats_recorded, ErrorCode::StatsAlreadyRecorded);
+
+        let same_side = temp.token_in_mint == stats.base_mint && temp.token_out_mint == stats.quote_mint;
+        let other_side = temp.token_in_mint == stats.quote_mint && temp.token_out_mint == stats.base_mint;
+        require!(same_side || other_side, ErrorCode::MintMismatch);
+
+        settlement.stats_recorded = true;
+        stats.pending = true;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let stats_key = stats.key();
+        let [base_lo, base_hi] = mint_limbs(&stats.base_mint);
+        let [quote_lo, quote_hi] = mint_limbs(&stats.quote_mint);
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::PlaintextU128(stats.state_nonce),
+            Argument::Account(stats_key, 8, 32 * PAIR_STATS_CIPHERTEXTS as u32),
+            Argument::PlaintextU128(base_lo),
+            Argument::PlaintextU128(base_hi),
+            Argument::PlaintextU128(quote_lo),
+            Argument::PlaintextU128(quote_hi),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RecordPairStatsCallback::callback_ix(&[CallbackAccount {
+                pubkey: stats_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "record_pair_stats", auto_serialize = false)]
+    pub fn record_pair_stats_callback(
+        ctx: Context<RecordPairStatsCallback>,
+        output: ComputationOutputs<EncryptedPairStats>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let stats = &mut ctx.accounts.pair_stats;
+        stats.encrypted_stats = state.ciphertexts;
+        stats.state_nonce = state.nonce;
+        stats.pending = false;
+
+        Ok(())
+    }
+
+    pub fn reveal_pair_stats(ctx: Context<RevealPairStats>, computation_offset: u64) -> Result<()> {
+        let stats = &mut ctx.accounts.pair_stats;
+        require!(!stats.pending, ErrorCode::StatsBusy);
+        require!(!stats.revealed, ErrorCode::StatsClosed);
+        require!(Clock::get()?.slot / STATS_EPOCH_SLOTS > stats.epoch, ErrorCode::StatsEpochNotOver);
+
+        stats.pending = true;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let stats_key = stats.key();
+        let args = vec![
+            Argument::PlaintextU128(stats.state_nonce),
+            Argument::Account(stats_key, 8, 32 * PAIR_STATS_CIPHERTEXTS as u32),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RevealPairStatsCallback::callback_ix(&[CallbackAccount {
+                pubkey: stats_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_pair_stats", auto_serialize = false)]
+    pub fn reveal_pair_stats_callback(
+        ctx: Context<RevealPairStatsCallback>,
+        output: ComputationOutputs<PairStatsBands>,
+    ) -> Result<()> {
+        let bands = match output {
+            ComputationOutputs::Success(bands) => bands,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let stats = &mut ctx.accounts.pair_stats;
+        stats.base_volume_band = bands.base_volume_band;
+        stats.quote_volume_band = bands.quote_volume_band;
+        stats.order_count_band = bands.order_count_band;
+        stats.revealed = true;
+        stats.pending = false;
+
+        emit!(PairStatsRevealedEvent {
+            base_mint: stats.base_mint,
+            quote_mint: stats.quote_mint,
+            epoch: stats.epoch,
+            base_volume_band: bands.base_volume_band,
+            quote_volume_band: bands.quote_volume_band,
+            order_count_band: bands.order_count_band,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_book_open_comp_def(ctx: Context<InitBookOpenCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_book_insert_comp_def(ctx: Context<InitBookInsertCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_book_cancel_comp_def(ctx: Context<InitBookCancelCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_book_match_comp_def(ctx: Context<InitBookMatchCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn open_order_book(
+        ctx: Context<OpenOrderBook>,
+        computation_offset: u64,
+        state_nonce: u128,
+    ) -> Result<()> {
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let book = &mut ctx.accounts.order_book;
+        book.base_mint = ctx.accounts.base_mint.key();
+        book.quote_mint = ctx.accounts.quote_mint.key();
+        book.pending = true;
+        book.pending_op = BookOp::Open;
+        book.pending_since = Clock::get()?.slot;
+        book.bump = ctx.bumps.order_book;
+        let book_key = book.key();
+
+        let args = vec![Argument::PlaintextU128(state_nonce)];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![BookOpenCallback::callback_ix(&[CallbackAccount {
+                pubkey: book_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "book_open", auto_serialize = false)]
+    pub fn book_open_callback(
+        ctx: Context<BookOpenCallback>,
+        output: ComputationOutputs<EncryptedOrderBook>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let book = &mut ctx.accounts.order_book;
+        book.encrypted_book = state.ciphertexts;
+        book.state_nonce = state.nonce;
+        book.pending = false;
+
+        Ok(())
+    }
+
+    pub fn rest_order(ctx: Context<RestOrder>, computation_offset: u64) -> Result<()> {
+        let temp = &mut ctx.accounts.temp_wallet;
+        let book = &mut ctx.accounts.order_book;
+        require!(temp.active, ErrorCode::SettlementNotActive);
+        require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require_pool_access(&ctx.accounts.pool_policy, temp, ctx.program_id)?;
+        require!(!book.pending, ErrorCode::BookBusy);
+        require!(book.trades_pair(temp.token_in_mint, temp.token_out_mint), ErrorCode::MintMismatch);
+
+        let slot = book
+            .slot_status
+            .iter()
+            .position(|status| *status == BookSlotStatus::Empty)
+            .ok_or(ErrorCode::BookFull)?;
+
+        let amount = temp.amount_in;
+        require!(ctx.accounts.temp_token_account.amount >= amount, ErrorCode::InsufficientFunds);
+
+        temp.is_funded = true;
+
+        // The maker's deposit sits in the book vault while the order rests.
+        let bump_arr = [temp.bump];
+        let nonce_bytes = temp.nonce.to_le_bytes();
+        let seeds = &[
+            b"temp_wallet",
+            temp.user.as_ref(),
+            nonce_bytes.as_ref(),
+            &bump_arr[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account.to_account_info(),
+                    to: ctx.accounts.book_vault_in.to_account_info(),
+                    authority: temp.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+        )?;
+
+        book.slots[slot] = temp.key();
+        book.slot_status[slot] = BookSlotStatus::Resting;
+        book.deposited[slot] = amount;
+        book.given[slot] = 0;
+        book.proceeds[slot] = 0;
+        book.pending = true;
+        book.pending_op = BookOp::Insert;
+        book.pending_since = Clock::get()?.slot;
+        book.pending_slot = slot as u8;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let book_key = book.key();
+        let [base_lo, base_hi] = mint_limbs(&book.base_mint);
+        let [quote_lo, quote_hi] = mint_limbs(&book.quote_mint);
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::PlaintextU128(book.state_nonce),
+            Argument::Account(book_key, 8, 32 * BOOK_CIPHERTEXTS as u32),
+            Argument::PlaintextU8(slot as u8),
+            Argument::PlaintextU64(amount),
+            Argument::PlaintextU128(base_lo),
+            Argument::PlaintextU128(base_hi),
+            Argument::PlaintextU128(quote_lo),
+            Argument::PlaintextU128(quote_hi),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![BookInsertCallback::callback_ix(&[CallbackAccount {
+                pubkey: book_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "book_insert", auto_serialize = false)]
+    pub fn book_insert_callback(
+        ctx: Context<BookInsertCallback>,
+        output: ComputationOutputs<BookInsertOutput>,
+    ) -> Result<()> {
+        let book = &mut ctx.accounts.order_book;
+        // Already given up on by release_stuck_book.
+        if !book.pending || book.pending_op != BookOp::Insert {
+            return Ok(());
+        }
+        book.pending = false;
+
+        let accepted = match output {
+            ComputationOutputs::Success(out) => {
+                book.encrypted_book = out.book.ciphertexts;
+                book.state_nonce = out.book.nonce;
+                out.accepted
+            }
+            _ => false,
+        };
+
+        // A rejected or failed order never rests; its deposit is released to
+        // withdraw.
+        let slot = book.pending_slot as usize;
+        if !accepted {
+            book.slot_status[slot] = BookSlotStatus::Closed;
+        }
+
+        emit!(OrderRestedEvent {
+            order_book: book.key(),
+            temp_wallet: book.slots[slot],
+            slot: slot as u8,
+            accepted,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_resting_order(
+        ctx: Context<CancelRestingOrder>,
+        computation_offset: u64,
+        slot: u8,
+    ) -> Result<()> {
+        let book = &mut ctx.accounts.order_book;
+        require!(!book.pending, ErrorCode::BookBusy);
+        require!((slot as usize) < BOOK_CAPACITY, ErrorCode::InvalidBookSlot);
+        require!(book.slot_status[slot as usize] == BookSlotStatus::Resting, ErrorCode::InvalidBookSlot);
+        require_keys_eq!(book.slots[slot as usize], ctx.accounts.temp_wallet.key(), ErrorCode::InvalidBookSlot);
+
+        book.pending = true;
+        book.pending_op = BookOp::Cancel;
+        book.pending_since = Clock::get()?.slot;
+        book.pending_slot = slot;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let book_key = book.key();
+        let args = vec![
+            Argument::PlaintextU128(book.state_nonce),
+            Argument::Account(book_key, 8, 32 * BOOK_CIPHERTEXTS as u32),
+            Argument::PlaintextU8(slot),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![BookCancelCallback::callback_ix(&[CallbackAccount {
+                pubkey: book_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "book_cancel", auto_serialize = false)]
+    pub fn book_cancel_callback(
+        ctx: Context<BookCancelCallback>,
+        output: ComputationOutputs<EncryptedOrderBook>,
+    ) -> Result<()> {
+        let book = &mut ctx.accounts.order_book;
+        // Already given up on by release_stuck_book.
+        if !book.pending || book.pending_op != BookOp::Cancel {
+            return Ok(());
+        }
+        book.pending = false;
+
+        // A failed cancel leaves the order resting; the maker can cancel again.
+        if let ComputationOutputs::Success(state) = output {
+            book.encrypted_book = state.ciphertexts;
+            book.state_nonce = state.nonce;
+            let slot = book.pending_slot as usize;
+            book.slot_status[slot] = BookSlotStatus::Closed;
+        }
+
+        Ok(())
+    }
+
+    pub fn withdraw_resting_order(ctx: Context<WithdrawRestingOrder>, slot: u8) -> Result<()> {
+        let book = &ctx.accounts.order_book;
+        let i = slot as usize;
+        require!(i < BOOK_CAPACITY, ErrorCode::InvalidBookSlot);
+        require!(book.slot_status[i] == BookSlotStatus::Closed, ErrorCode::InvalidBookSlot);
+        require_keys_eq!(book.slots[i], ctx.accounts.temp_wallet.key(), ErrorCode::InvalidBookSlot);
+
+        let proceeds = book.proceeds[i];
+        let refund = book.deposited[i].saturating_sub(book.given[i]);
+
+        let bump_arr = [book.bump];
+        let seeds = &[
+            b"order_book",
+            book.base_mint.as_ref(),
+            book.quote_mint.as_ref(),
+            &bump_arr[..],
+        ];
+        let signer = &[&seeds[..]];
+
+        if proceeds > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.book_vault_out.to_account_info(),
+                        to: ctx.accounts.temp_token_account_out.to_account_info(),
+                        authority: ctx.accounts.order_book.to_account_info(),
+                    },
+                    signer,
+                ),
+                proceeds,
+            )?;
+        }
+
+        if refund > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.book_vault_in.to_account_info(),
+                        to: ctx.accounts.temp_token_account_in.to_account_info(),
+                        authority: ctx.accounts.order_book.to_account_info(),
+                    },
+                    signer,
+                ),
+                refund,
+            )?;
+        }
+
+        let book = &mut ctx.accounts.order_book;
+        book.slots[i] = Pubkey::default();
+        book.slot_status[i] = BookSlotStatus::Empty;
+        book.deposited[i] = 0;
+        book.given[i] = 0;
+        book.proceeds[i] = 0;
+
+        Ok(())
+    }
+
+    pub fn match_against_book(ctx: Context<MatchAgainstBook>, computation_offset: u64) -> Result<()> {
+        let temp = &mut ctx.accounts.temp_wallet;
+        let book = &mut ctx.accounts.order_book;
+        require!(temp.active, ErrorCode::SettlementNotActive);
+        require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require_pool_access(&ctx.accounts.pool_policy, temp, ctx.program_id)?;
+        require!(!book.pending, ErrorCode::BookBusy);
+        require!(book.trades_pair(temp.token_in_mint, temp.token_out_mint), ErrorCode::MintMismatch);
+
+        let amount = temp.amount_in;
+        require!(ctx.accounts.temp_token_account.amount >= amount, ErrorCode::InsufficientFunds);
+
+        temp.is_funded = true;
+
+        // Escrow the taker up front so a maker can be paid from the vault as
+        // soon as the match lands.
+        let bump_arr = [temp.bump];
+        let nonce_bytes = temp.nonce.to_le_bytes();
+        let seeds = &[
+            b"temp_wallet",
+            temp.user.as_ref(),
+            nonce_bytes.as_ref(),
+            &bump_arr[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account.to_account_info(),
+                    to: ctx.accounts.book_vault_in.to_account_info(),
+                    authority: temp.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+        )?;
+
+        let book_match = &mut ctx.accounts.book_match;
+        book_match.order_book = book.key();
+        book_match.temp_wallet = temp.key();
+        book_match.deposit = amount;
+        book_match.resolved = false;
+        book_match.bump = ctx.bumps.book_match;
+        let book_match_key = book_match.key();
+
+        book.pending = true;
+        book.pending_op = BookOp::Match;
+        book.pending_since = Clock::get()?.slot;
+        book.pending_match = book_match_key;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let book_key = book.key();
+        let [base_lo, base_hi] = mint_limbs(&book.base_mint);
+        let [quote_lo, quote_hi] = mint_limbs(&book.quote_mint);
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::PlaintextU128(book.state_nonce),
+            Argument::Account(book_key, 8, 32 * BOOK_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(amount),
+            Argument::PlaintextU128(base_lo),
+            Argument::PlaintextU128(base_hi),
+            Argument::PlaintextU128(quote_lo),
+            Argument::PlaintextU128(quote_hi),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![BookMatchCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: book_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: book_match_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "book_match", auto_serialize = false)]
+    pub fn book_match_callback(
+        ctx: Context<BookMatchCallback>,
+        output: ComputationOutputs<BookMatchOutput>,
+    ) -> Result<()> {
+        let book = &mut ctx.accounts.order_book;
+        let book_match = &mut ctx.accounts.book_match;
+        // Already given up on by release_stuck_book.
+        if !book.pending || book.pending_op != BookOp::Match || book.pending_match != book_match.key() {
+            return Ok(());
+        }
+        book.pending = false;
+
+        // Both sides are bound to their deposits inside the circuit; check it
+        // again here so a fill can never pay out of someone else's escrow. A
+        // fill that fails the check is dropped with the rest of the output and
+        // the taker is refunded in full.
+        let fill = match output {
+            ComputationOutputs::Success(out) if book_fill_fits(book, book_match.deposit, &out.fill) => {
+                book.encrypted_book = out.book.ciphertexts;
+                book.state_nonce = out.book.nonce;
+                out.fill
+            }
+            _ => BookFill {
+                matched: false,
+                slot: 0,
+                taker_gives: 0,
+                taker_receives: 0,
+            },
+        };
+        if fill.matched {
+            let slot = fill.slot as usize;
+            book.given[slot] += fill.taker_receives;
+            book.proceeds[slot] += fill.taker_gives;
+        }
+
+        resolve_book_match(book, book_match, fill);
+
+        Ok(())
+    }
+
+    /// Permissionless. Resolves an order book computation that never came
+    /// back: a stuck insert closes the slot so the maker can withdraw, a stuck
+    /// match refunds the taker, and a stuck cancel leaves the order resting.
+    pub fn release_stuck_book(ctx: Context<ReleaseStuckBook>) -> Result<()> {
+        let book = &mut ctx.accounts.order_book;
+        // A book that never opened holds no deposits, so there is nothing to
+        // release.
+        require!(book.pending && book.pending_op != BookOp::Open, ErrorCode::BookNotStuck);
+        require!(
+            Clock::get()?.slot > book.pending_since + BOOK_PENDING_TIMEOUT_SLOTS,
+            ErrorCode::BookNotStuck
+        );
+        book.pending = false;
+
+        match book.pending_op {
+            BookOp::Insert => {
+                let slot = book.pending_slot as usize;
+                book.slot_status[slot] = BookSlotStatus::Closed;
+                emit!(OrderRestedEvent {
+                    order_book: book.key(),
+                    temp_wallet: book.slots[slot],
+                    slot: slot as u8,
+                    accepted: false,
+                });
+            }
+            BookOp::Match => {
+                let info = &ctx.accounts.book_match;
+                require_keys_eq!(info.key(), book.pending_match, ErrorCode::InvalidBookMatch);
+                let mut data = info.try_borrow_mut_data()?;
+                let mut book_match: BookMatch = AccountDeserialize::try_deserialize(&mut &data[..])?;
+                let unmatched = BookFill {
+                    matched: false,
+                    slot: 0,
+                    taker_gives: 0,
+                    taker_receives: 0,
+                };
+                resolve_book_match(book, &mut book_match, unmatched);
+                book_match.try_serialize(&mut *data)?;
+            }
+            BookOp::Open | BookOp::Cancel => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn settle_book_match(ctx: Context<SettleBookMatch>) -> Result<()> {
+        let book_match = &ctx.accounts.book_match;
+        require!(book_match.resolved, ErrorCode::BookMatchPending);
+
+        let receives = book_match.taker_receives;
+        let refund = book_match.deposit.saturating_sub(book_match.taker_gives);
+
+        let book = &ctx.accounts.order_book;
+        let bump_arr = [book.bump];
+        let seeds = &[
+            b"order_book",
+            book.base_mint.as_ref(),
+            book.quote_mint.as_ref(),
+            &bump_arr[..],
+        ];
+        let signer = &[&seeds[..]];
+
+        if receives > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.book_vault_out.to_account_info(),
+                        to: ctx.accounts.temp_token_account_out.to_account_info(),
+                        authority: ctx.accounts.order_book.to_account_info(),
+                    },
+                    signer,
+                ),
+                receives,
+            )?;
+        }
+
+        if refund > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.book_vault_in.to_account_info(),
+                        to: ctx.accounts.temp_token_account_in.to_account_info(),
+                        authority: ctx.accounts.order_book.to_account_info(),
+                    },
+                    signer,
+                ),
+                refund,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn init_open_remainder_comp_def(ctx: Context<InitOpenRemainderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_fill_round_comp_def(ctx: Context<InitFillRoundCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Switches a matched order to being worked over several execute_swap
+    /// rounds until its encrypted remainder is used up or `expires_at_slot`.
+    pub fn start_partial_fill(
+        ctx: Context<StartPartialFill>,
+        computation_offset: u64,
+        expires_at_slot: u64,
+        state_nonce: u128,
+    ) -> Result<()> {
+        require!(expires_at_slot > Clock::get()?.slot, ErrorCode::PartialFillExpired);
+
+        require!(!ctx.accounts.temp_wallet.order_type.worked_in_rounds(), ErrorCode::RoundBasedOrder);
+        let settlement = &mut ctx.accounts.settlement_request;
+        require!(
+            settlement.active && !settlement.partial && settlement.fill_status == FillStatus::Pending,
+            ErrorCode::PartialFillUnavailable
+        );
+
+        // Hold off execute_swap until the remainder exists.
+        settlement.active = false;
+        settlement.partial = true;
+        settlement.expires_at_slot = expires_at_slot;
+        let settlement_key = settlement.key();
+
+        let partial_fill = &mut ctx.accounts.partial_fill;
+        partial_fill.nonce = settlement.nonce;
+        partial_fill.bump = ctx.bumps.partial_fill;
+        let partial_fill_key = partial_fill.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let mut args = swap_order_args(&ctx.accounts.temp_wallet);
+        args.push(Argument::PlaintextU128(state_nonce));
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![OpenRemainderCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: partial_fill_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: settlement_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "open_remainder", auto_serialize = false)]
+    pub fn open_remainder_callback(
+        ctx: Context<OpenRemainderCallback>,
+        output: ComputationOutputs<EncryptedRemainder>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let partial_fill = &mut ctx.accounts.partial_fill;
+        partial_fill.encrypted_remainder = state.ciphertexts;
+        partial_fill.state_nonce = state.nonce;
+
+        ctx.accounts.settlement_request.active = true;
+
+        Ok(())
+    }
+
+    pub fn verify_partial_fill(ctx: Context<VerifyPartialFill>, computation_offset: u64) -> Result<()> {
+        let settlement = &ctx.accounts.settlement_request;
+        require!(settlement.partial, ErrorCode::PartialFillUnavailable);
+        require!(
+            settlement.fill_status == FillStatus::AwaitingVerification,
+            ErrorCode::FillNotAwaitingVerification
+        );
+        let settlement_key = settlement.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let partial_fill_key = ctx.accounts.partial_fill.key();
+        let args = vec![
+            Argument::PlaintextU128(ctx.accounts.partial_fill.state_nonce),
+            Argument::Account(partial_fill_key, 8, 32 * REMAINDER_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(settlement.spent_in),
+            Argument::PlaintextU64(settlement.realized_out),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![FillRoundCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: partial_fill_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: settlement_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "fill_round", auto_serialize = false)]
+    pub fn fill_round_callback(
+        ctx: Context<FillRoundCallback>,
+        output: ComputationOutputs<FillRoundOutput>,
+    ) -> Result<()> {
+        let FillRoundOutput { remainder, check } = match output {
+            ComputationOutputs::Success(out) => out,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let partial_fill = &mut ctx.accounts.partial_fill;
+        partial_fill.encrypted_remainder = remainder.ciphertexts;
+        partial_fill.state_nonce = remainder.nonce;
+        partial_fill.rounds += 1;
+
+        // A passing round that leaves something over re-arms the request for
+        // the next execute_swap; expiry is enforced there.
+        let settlement = &mut ctx.accounts.settlement_request;
+        if !check.passed {
+            settlement.fill_status = FillStatus::Failed;
+        } else if check.done {
+            settlement.fill_status = FillStatus::Verified;
+        } else {
+            settlement.fill_status = FillStatus::Pending;
+            settlement.active = true;
+        }
+
+        emit!(PartialFillEvent {
+            nonce: settlement.nonce,
+            round: partial_fill.rounds,
+            passed: check.passed,
+            done: check.done,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_check_trigger_comp_def(ctx: Context<InitCheckTriggerCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Keeper crank for triggered orders: feeds the current oracle price into MPC,
+    /// which reveals only whether the hidden threshold has been crossed.
+    pub fn check_trigger(ctx: Context<CheckTrigger>, computation_offset: u64) -> Result<()> {
+        let settlement = &ctx.accounts.settlement_request;
+        require!(settlement.awaiting_trigger, ErrorCode::NotAwaitingTrigger);
+        let settlement_key = settlement.key();
+
+        let oracle_price = read_pyth_price(&ctx.accounts.price_feed, Clock::get()?.slot)?;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let mut args = swap_order_args(&ctx.accounts.temp_wallet);
+        args.extend([
+            Argument::PlaintextU64(oracle_price),
+            Argument::PlaintextBool(ctx.accounts.temp_wallet.trigger_above),
+            Argument::PlaintextU8(ctx.accounts.temp_wallet.order_type as u8),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CheckTriggerCallback::callback_ix(&[CallbackAccount {
+                pubkey: settlement_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "check_trigger", auto_serialize = false)]
+    pub fn check_trigger_callback(
+        ctx: Context<CheckTriggerCallback>,
+        output: ComputationOutputs<TriggerCheck>,
+    ) -> Result<()> {
+        let o = match output {
+            ComputationOutputs::Success(o) => o,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let (pda, _bump) = Pubkey::find_program_address(
+            &[b"settlement", o.nonce.to_le_bytes().as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(ctx.accounts.settlement_request.key(), pda, ErrorCode::InvalidSettlementPDA);
+
+        emit!(TriggerCheckedEvent {
+            nonce: o.nonce,
+            fired: o.fire,
+        });
+
+        let settlement = &mut ctx.accounts.settlement_request;
+        if !o.fire || !settlement.awaiting_trigger {
+            return Ok(());
+        }
+
+        settlement.awaiting_trigger = false;
+        settlement.triggered = true;
+        settlement.active = true;
+
+        // From here on the order is routed like any market order.
+        emit!(OrderSettledEvent {
+            amount_in: settlement.amount_in,
+            token_in: settlement.token_in,
+            token_out: settlement.token_out,
+            nonce: o.nonce,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_open_twap_comp_def(ctx: Context<InitOpenTwapCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_next_slice_comp_def(ctx: Context<InitNextSliceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Sets up the encrypted schedule for a matched TWAP order. The slice
+    /// count is encrypted under the same x25519 key as the order.
+    pub fn start_twap(
+        ctx: Context<StartTwap>,
+        computation_offset: u64,
+        interval_slots: u64,
+        encrypted_slices: [u8; 32],
+        slices_nonce: u128,
+        state_nonce: u128,
+    ) -> Result<()> {
+        let temp = &ctx.accounts.temp_wallet;
+        require!(temp.order_type == OrderType::Twap, ErrorCode::NotTwapOrder);
+        require!(interval_slots > 0, ErrorCode::InvalidTwapInterval);
+
+        let settlement = &ctx.accounts.settlement_request;
+        require!(settlement.amount_in > 0, ErrorCode::SettlementNotActive);
+        require!(settlement.fill_status == FillStatus::Pending, ErrorCode::TwapFinished);
+
+        let schedule = &mut ctx.accounts.twap_schedule;
+        schedule.nonce = settlement.nonce;
+        schedule.temp_wallet = temp.key();
+        schedule.interval_slots = interval_slots;
+        schedule.next_slot = Clock::get()?.slot;
+        schedule.pending = true;
+        schedule.bump = ctx.bumps.twap_schedule;
+        let schedule_key = schedule.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let temp = &ctx.accounts.temp_wallet;
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::ArcisPubkey(temp.encryption_pubkey),
+            Argument::PlaintextU128(slices_nonce),
+            Argument::EncryptedU64(encrypted_slices),
+            Argument::PlaintextU128(state_nonce),
+        ]);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![OpenTwapCallback::callback_ix(&[CallbackAccount {
+                pubkey: schedule_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "open_twap", auto_serialize = false)]
+    pub fn open_twap_callback(
+        ctx: Context<OpenTwapCallback>,
+        output: ComputationOutputs<EncryptedTwapState>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let schedule = &mut ctx.accounts.twap_schedule;
+        schedule.encrypted_state = state.ciphertexts;
+        schedule.state_nonce = state.nonce;
+        schedule.pending = false;
+
+        Ok(())
+    }
+
+    /// Keeper crank: once the cadence allows, checks the slice executed since
+    /// the last release and reveals the size of the next one.
+    pub fn release_twap_slice(ctx: Context<ReleaseTwapSlice>, computation_offset: u64) -> Result<()> {
+        let schedule = &mut ctx.accounts.twap_schedule;
+        require!(!schedule.pending, ErrorCode::TwapBusy);
+        require!(!schedule.finished, ErrorCode::TwapFinished);
+        require!(Clock::get()?.slot >= schedule.next_slot, ErrorCode::TwapSliceNotDue);
+
+        // A slice the keeper never routed counts as spending nothing; its
+        // size rolls into the slices after it.
+        let settlement = &mut ctx.accounts.settlement_request;
+        let executed = settlement.fill_status == FillStatus::AwaitingVerification;
+        let spent_in = if executed { settlement.spent_in } else { 0 };
+        let realized_out = if executed { settlement.realized_out } else { 0 };
+        settlement.active = false;
+        let settlement_key = settlement.key();
+
+        schedule.pending = true;
+        let schedule_key = schedule.key();
+        let state_nonce = schedule.state_nonce;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = vec![
+            Argument::PlaintextU128(state_nonce),
+            Argument::Account(schedule_key, 8, 32 * TWAP_STATE_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(spent_in),
+            Argument::PlaintextU64(realized_out),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![NextSliceCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: schedule_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: settlement_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "next_slice", auto_serialize = false)]
+    pub fn next_slice_callback(
+        ctx: Context<NextSliceCallback>,
+        output: ComputationOutputs<NextSliceOutput>,
+    ) -> Result<()> {
+        let NextSliceOutput { state, release } = match output {
+            ComputationOutputs::Success(out) => out,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let schedule = &mut ctx.accounts.twap_schedule;
+        schedule.encrypted_state = state.ciphertexts;
+        schedule.state_nonce = state.nonce;
+        schedule.pending = false;
+
+        let settlement = &mut ctx.accounts.settlement_request;
+        settlement.spent_in = 0;
+        settlement.realized_out = 0;
+        settlement.slice_amount = release.amount;
+
+        if !release.passed {
+            settlement.fill_status = FillStatus::Failed;
+            schedule.finished = true;
+        } else if release.done {
+            settlement.fill_status = FillStatus::Verified;
+            schedule.finished = true;
+        } else {
+            // A released slice is the TWAP's trigger for execute_swap.
+            settlement.fill_status = FillStatus::Pending;
+            settlement.triggered = true;
+            settlement.active = true;
+            schedule.slices_released += 1;
+            schedule.next_slot = Clock::get()?.slot + schedule.interval_slots;
+        }
+
+        emit!(TwapSliceEvent {
+            nonce: settlement.nonce,
+            slice: schedule.slices_released,
+            amount: release.amount,
+            passed: release.passed,
+            done: release.done,
+        });
+
+        if settlement.active {
+            emit!(OrderSettledEvent {
+                amount_in: release.amount,
+                token_in: settlement.token_in,
+                token_out: settlement.token_out,
+                nonce: settlement.nonce,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn init_refill_clip_comp_def(ctx: Context<InitRefillClipCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Keeper crank for iceberg orders: checks the clip that just traded and
+    /// reveals the next one from the hidden remainder.
+    pub fn refill_clip(ctx: Context<RefillClip>, computation_offset: u64) -> Result<()> {
+        require!(ctx.accounts.temp_wallet.order_type == OrderType::Iceberg, ErrorCode::NotIcebergOrder);
+
+        let settlement = &mut ctx.accounts.settlement_request;
+        require!(
+            settlement.fill_status == FillStatus::AwaitingVerification,
+            ErrorCode::FillNotAwaitingVerification
+        );
+        let spent_in = settlement.spent_in;
+        let realized_out = settlement.realized_out;
+        let state_nonce = settlement.iceberg_nonce;
+        // Keeps a second crank from counting the same clip twice.
+        settlement.fill_status = FillStatus::Pending;
+        let settlement_key = settlement.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = vec![
+            Argument::PlaintextU128(state_nonce),
+            Argument::Account(settlement_key, 8, 32 * ICEBERG_STATE_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(spent_in),
+            Argument::PlaintextU64(realized_out),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RefillClipCallback::callback_ix(&[CallbackAccount {
+                pubkey: settlement_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "refill_clip", auto_serialize = false)]
+    pub fn refill_clip_callback(
+        ctx: Context<RefillClipCallback>,
+        output: ComputationOutputs<RefillClipOutput>,
+    ) -> Result<()> {
+        let RefillClipOutput { state, release } = match output {
+            ComputationOutputs::Success(out) => out,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let settlement = &mut ctx.accounts.settlement_request;
+        settlement.encrypted_iceberg = state.ciphertexts;
+        settlement.iceberg_nonce = state.nonce;
+        settlement.spent_in = 0;
+        settlement.realized_out = 0;
+        settlement.slice_amount = release.clip;
+
+        if !release.passed {
+            settlement.fill_status = FillStatus::Failed;
+        } else if release.done {
+            settlement.fill_status = FillStatus::Verified;
+        } else {
+            settlement.amount_in = release.clip;
+            settlement.active = true;
+        }
+
+        emit!(IcebergRefilledEvent {
+            nonce: settlement.nonce,
+            clip: release.clip,
+            passed: release.passed,
+            done: release.done,
+        });
+
+        if settlement.active {
+            emit!(OrderSettledEvent {
+                amount_in: release.clip,
+                token_in: settlement.token_in,
+                token_out: settlement.token_out,
+                nonce: settlement.nonce,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn init_select_quote_comp_def(ctx: Context<InitSelectQuoteCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Creates the escrow PDA a market maker quotes from. Inventory is
+    /// deposited with plain transfers into the escrow's token accounts.
+    pub fn register_market_maker(ctx: Context<RegisterMarketMaker>) -> Result<()> {
+        let escrow = &mut ctx.accounts.maker_escrow;
+        escrow.authority = ctx.accounts.authority.key();
+        escrow.bump = ctx.bumps.maker_escrow;
+        Ok(())
+    }
+
+    /// Inventory is locked while any of the maker's quotes is still open, so
+    /// a quote can't be emptied out between selection and settlement.
+    pub fn withdraw_maker_inventory(ctx: Context<WithdrawMakerInventory>, amount: u64) -> Result<()> {
+        let escrow = &ctx.accounts.maker_escrow;
+        require!(escrow.open_quotes == 0, ErrorCode::MakerInventoryLocked);
+        let bump_arr = [escrow.bump];
+        let seeds = &[b"maker_escrow", escrow.authority.as_ref(), &bump_arr[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    to: ctx.accounts.authority_token_account.to_account_info(),
+                    authority: ctx.accounts.maker_escrow.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Puts a funded TempWallet's order out for sealed quotes until
+    /// `quote_window_slots` have passed.
+    pub fn open_rfq(ctx: Context<OpenRfq>, quote_window_slots: u64) -> Result<()> {
+        let temp = &mut ctx.accounts.temp_wallet;
+        require!(temp.active, ErrorCode::SettlementNotActive);
+        require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require!(temp.order_type == OrderType::Market, ErrorCode::RfqMarketOnly);
+        require_pool_access(&ctx.accounts.pool_policy, temp, ctx.program_id)?;
+        require!(ctx.accounts.temp_token_account.amount >= temp.amount_in, ErrorCode::InsufficientFunds);
+
+        // The deposit stays in the TempWallet; it only moves at settlement.
+        temp.is_funded = true;
+
+        let rfq = &mut ctx.accounts.rfq;
+        rfq.temp_wallet = temp.key();
+        rfq.deadline_slot = Clock::get()?.slot + quote_window_slots;
+        rfq.bump = ctx.bumps.rfq;
+
+        Ok(())
+    }
+
+    pub fn submit_quote(
+        ctx: Context<SubmitQuote>,
+        encrypted_amount_out: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        let rfq = &mut ctx.accounts.rfq;
+        let maker = ctx.accounts.maker_escrow.key();
+        require!(!rfq.pending && !rfq.resolved, ErrorCode::RfqClosed);
+        require!(Clock::get()?.slot <= rfq.deadline_slot, ErrorCode::RfqClosed);
+        require!((rfq.count as usize) < RFQ_QUOTES, ErrorCode::RfqFull);
+        require!(
+            !rfq.makers[..rfq.count as usize].contains(&maker),
+            ErrorCode::DuplicateQuote
+        );
+
+        let i = rfq.count as usize;
+        rfq.makers[i] = maker;
+        rfq.quotes[i] = encrypted_amount_out;
+        rfq.quote_keys[i] = encryption_pubkey;
+        rfq.quote_nonces[i] = encryption_nonce;
+        rfq.count += 1;
+
+        // Held until release_quote, or settle_rfq for the winner.
+        ctx.accounts.maker_escrow.open_quotes += 1;
+
+        emit!(QuoteSubmittedEvent {
+            rfq: rfq.key(),
+            maker,
+            count: rfq.count,
+        });
+
+        Ok(())
+    }
+
+    /// Closes the quote window and lets MPC pick the winner. The remaining
+    /// accounts are each quoting maker's token_out escrow account, in quote
+    /// order, so the circuit can skip quotes a maker can't cover.
+    pub fn select_quote<'info>(
+        ctx: Context<'_, '_, '_, 'info, SelectQuote<'info>>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let rfq = &ctx.accounts.rfq;
+        require!(!rfq.pending && !rfq.resolved, ErrorCode::RfqClosed);
+        require!(rfq.count > 0, ErrorCode::RfqNotReady);
+        let window_over = Clock::get()?.slot > rfq.deadline_slot;
+        require!(rfq.count as usize == RFQ_QUOTES || window_over, ErrorCode::RfqNotReady);
+        require!(ctx.remaining_accounts.len() == rfq.count as usize, ErrorCode::InvalidMakerAccount);
+
+        let temp = &ctx.accounts.temp_wallet;
+        let mut balances = [0u64; RFQ_QUOTES];
+        for (i, info) in ctx.remaining_accounts.iter().enumerate() {
+            let expected = get_associated_token_address(
+                &rfq.makers[i],
+                &temp.token_out_mint,
+            );
+            require_keys_eq!(info.key(), expected, ErrorCode::InvalidMakerAccount);
+            let account: Account<TokenAccount> = Account::try_from(info)?;
+            balances[i] = account.amount;
+        }
+
+        let [token_in_lo, token_in_hi] = mint_limbs(&temp.token_in_mint);
+        let [token_out_lo, token_out_hi] = mint_limbs(&temp.token_out_mint);
+        let mut args = swap_order_args(temp);
+        args.extend([
+            Argument::PlaintextU64(temp.amount_in),
+            Argument::PlaintextU128(token_in_lo),
+            Argument::PlaintextU128(token_in_hi),
+            Argument::PlaintextU128(token_out_lo),
+            Argument::PlaintextU128(token_out_hi),
+        ]);
+        // Unused slots still need well-formed inputs; `count` masks them out.
+        for i in 0..RFQ_QUOTES {
+            let used = i < rfq.count as usize;
+            args.extend([
+                Argument::ArcisPubkey(if used { rfq.quote_keys[i] } else { temp.encryption_pubkey }),
+                Argument::PlaintextU128(if used { rfq.quote_nonces[i] } else { 0 }),
+                Argument::EncryptedU64(if used { rfq.quotes[i] } else { [0; 32] }),
+            ]);
+        }
+        args.extend(balances.map(Argument::PlaintextU64));
+        args.push(Argument::PlaintextU8(rfq.count));
+
+        let rfq_key = rfq.key();
+        ctx.accounts.rfq.pending = true;
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![SelectQuoteCallback::callback_ix(&[CallbackAccount {
+                pubkey: rfq_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "select_quote", auto_serialize = false)]
+    pub fn select_quote_callback(
+        ctx: Context<SelectQuoteCallback>,
+        output: ComputationOutputs<RfqResult>,
+    ) -> Result<()> {
+        let o = match output {
+            ComputationOutputs::Success(o) => o,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let rfq = &mut ctx.accounts.rfq;
+        rfq.pending = false;
+        rfq.resolved = true;
+        rfq.filled = o.filled;
+        rfq.winner = o.winner;
+        rfq.amount_out = o.amount_out;
+
+        emit!(RfqResolvedEvent {
+            rfq: rfq.key(),
+            filled: o.filled,
+            maker: if o.filled { rfq.makers[o.winner as usize] } else { Pubkey::default() },
+            amount_out: o.amount_out,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless crank that unlocks a maker's inventory once its quote
+    /// can no longer win: every quote of an unfilled RFQ, and the losing
+    /// quotes of a filled one.
+    pub fn release_quote(ctx: Context<ReleaseQuote>) -> Result<()> {
+        let rfq = &mut ctx.accounts.rfq;
+        require!(rfq.resolved, ErrorCode::RfqNotReady);
+        let maker = ctx.accounts.maker_escrow.key();
+        let i = rfq.makers[..rfq.count as usize]
+            .iter()
+            .position(|m| *m == maker)
+            .ok_or(ErrorCode::InvalidMakerAccount)?;
+        require!(!rfq.released[i], ErrorCode::QuoteReleased);
+        require!(!rfq.filled || i != rfq.winner as usize, ErrorCode::InvalidMakerAccount);
+
+        rfq.released[i] = true;
+        let escrow = &mut ctx.accounts.maker_escrow;
+        escrow.open_quotes = escrow.open_quotes.saturating_sub(1);
+
+        Ok(())
+    }
+
+    /// Swaps the taker's deposit for the winning quote between the TempWallet
+    /// and the maker's escrow. Losing quotes must be released first, since
+    /// the RFQ is closed here. Unfilled RFQs are unwound with
+    /// refund_tokens_to_user.
+    pub fn settle_rfq(ctx: Context<SettleRfq>) -> Result<()> {
+        let rfq = &ctx.accounts.rfq;
+        require!(rfq.resolved && rfq.filled, ErrorCode::RfqNotFilled);
+        let winner = rfq.winner as usize;
+        require!(
+            (0..rfq.count as usize).all(|i| i == winner || rfq.released[i]),
+            ErrorCode::QuotesNotReleased
+        );
+        let amount_out = rfq.amount_out;
+        let amount_in = ctx.accounts.temp_wallet.amount_in;
+
+        let escrow = &mut ctx.accounts.maker_escrow;
+        escrow.open_quotes = escrow.open_quotes.saturating_sub(1);
+
+        let temp = &ctx.accounts.temp_wallet;
+        let temp_bump = [temp.bump];
+        let nonce_bytes = temp.nonce.to_le_bytes();
+        let temp_seeds = &[
+            b"temp_wallet",
+            temp.user.as_ref(),
+            nonce_bytes.as_ref(),
+            &temp_bump[..],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.temp_token_account_in.to_account_info(),
+                    to: ctx.accounts.maker_token_account_in.to_account_info(),
+                    authority: ctx.accounts.temp_wallet.to_account_info(),
+                },
+                &[&temp_seeds[..]],
+            ),
+            amount_in,
+        )?;
+
+        let escrow = &ctx.accounts.maker_escrow;
+        let escrow_bump = [escrow.bump];
+        let escrow_seeds = &[b"maker_escrow", escrow.authority.as_ref(), &escrow_bump[..]];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.maker_token_account_out.to_account_info(),
+                    to: ctx.accounts.temp_token_account_out.to_account_info(),
+                    authority: ctx.accounts.maker_escrow.to_account_info(),
+                },
+                &[&escrow_seeds[..]],
+            ),
+            amount_out,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn init_check_otc_terms_comp_def(ctx: Context<InitCheckOtcTermsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Opens a block trade between the signer (party A, giving `mint_a`) and
+    /// a named counterparty (party B, giving `mint_b`).
+    pub fn propose_otc(
+        ctx: Context<ProposeOtc>,
+        nonce: u64,
+        counterparty: Pubkey,
+        expires_at_slot: u64,
+    ) -> Result<()> {
+        require!(counterparty != ctx.accounts.party_a.key(), ErrorCode::InvalidCounterparty);
+        require!(expires_at_slot > Clock::get()?.slot, ErrorCode::OtcExpired);
+        require!(ctx.accounts.mint_a.key() != ctx.accounts.mint_b.key(), ErrorCode::MintMismatch);
+
+        let deal = &mut ctx.accounts.otc_deal;
+        deal.party_a = ctx.accounts.party_a.key();
+        deal.party_b = counterparty;
+        deal.mint_a = ctx.accounts.mint_a.key();
+        deal.mint_b = ctx.accounts.mint_b.key();
+        deal.nonce = nonce;
+        deal.expires_at_slot = expires_at_slot;
+        deal.bump = ctx.bumps.otc_deal;
+
+        Ok(())
+    }
+
+    /// Deposits one side's tokens into its escrow along with its encrypted
+    /// terms. Either party may fund first.
+    pub fn fund_otc_escrow(
+        ctx: Context<FundOtcEscrow>,
+        amount: u64,
+        encrypted_give: [u8; 32],
+        encrypted_want_min: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOrderAmount);
+        let owner = ctx.accounts.owner.key();
+        let deal = &mut ctx.accounts.otc_deal;
+        require!(!deal.pending && !deal.resolved, ErrorCode::OtcClosed);
+        require!(Clock::get()?.slot <= deal.expires_at_slot, ErrorCode::OtcExpired);
+
+        if owner == deal.party_a {
+            deal.funded_a = true;
+        } else {
+            deal.funded_b = true;
+        }
+
+        let escrow = &mut ctx.accounts.otc_escrow;
+        escrow.deal = deal.key();
+        escrow.owner = owner;
+        escrow.mint = ctx.accounts.mint.key();
+        escrow.amount = amount;
+        escrow.encrypted_give = encrypted_give;
+        escrow.encrypted_want_min = encrypted_want_min;
+        escrow.encryption_pubkey = encryption_pubkey;
+        escrow.encryption_nonce = encryption_nonce;
+        escrow.bump = ctx.bumps.otc_escrow;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.escrow_token_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn check_otc_terms(ctx: Context<CheckOtcTerms>, computation_offset: u64) -> Result<()> {
+        let deal = &ctx.accounts.otc_deal;
+        require!(deal.funded_a && deal.funded_b, ErrorCode::OtcNotFunded);
+        require!(!deal.pending && !deal.resolved, ErrorCode::OtcClosed);
+        require!(Clock::get()?.slot <= deal.expires_at_slot, ErrorCode::OtcExpired);
+
+        let a = &ctx.accounts.escrow_a;
+        let b = &ctx.accounts.escrow_b;
+        let args = vec![
+            Argument::ArcisPubkey(a.encryption_pubkey),
+            Argument::PlaintextU128(a.encryption_nonce),
+            Argument::EncryptedU64(a.encrypted_give),
+            Argument::EncryptedU64(a.encrypted_want_min),
+            Argument::ArcisPubkey(b.encryption_pubkey),
+            Argument::PlaintextU128(b.encryption_nonce),
+            Argument::EncryptedU64(b.encrypted_give),
+            Argument::EncryptedU64(b.encrypted_want_min),
+            Argument::PlaintextU64(a.amount),
+            Argument::PlaintextU64(b.amount),
+        ];
+
+        let deal_key = deal.key();
+        ctx.accounts.otc_deal.pending = true;
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CheckOtcTermsCallback::callback_ix(&[CallbackAccount {
+                pubkey: deal_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "check_otc_terms", auto_serialize = false)]
+    pub fn check_otc_terms_callback(
+        ctx: Context<CheckOtcTermsCallback>,
+        output: ComputationOutputs<OtcMatch>,
+    ) -> Result<()> {
+        let o = match output {
+            ComputationOutputs::Success(o) => o,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let deal = &mut ctx.accounts.otc_deal;
+        deal.pending = false;
+        deal.resolved = true;
+        deal.compatible = o.compatible;
+        deal.amount_a = o.amount_a;
+        deal.amount_b = o.amount_b;
+
+        emit!(OtcCheckedEvent {
+            deal: deal.key(),
+            compatible: o.compatible,
+        });
+
+        Ok(())
+    }
+
+    /// Swaps the agreed amounts out of both escrows in one transaction.
+    /// Anything either side over-deposited is left for close_otc_escrow.
+    pub fn settle_otc(ctx: Context<SettleOtc>) -> Result<()> {
+        let deal = &ctx.accounts.otc_deal;
+        require!(deal.resolved && deal.compatible, ErrorCode::OtcNotCompatible);
+        require!(!deal.settled, ErrorCode::AlreadySettled);
+        let deal_key = deal.key();
+        let (amount_a, amount_b) = (deal.amount_a, deal.amount_b);
+
+        let escrow_a = &ctx.accounts.escrow_a;
+        let bump_a = [escrow_a.bump];
+        let seeds_a = &[b"otc_escrow", deal_key.as_ref(), escrow_a.owner.as_ref(), &bump_a[..]];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_a_token_account.to_account_info(),
+                    to: ctx.accounts.party_b_token_account.to_account_info(),
+                    authority: ctx.accounts.escrow_a.to_account_info(),
+                },
+                &[&seeds_a[..]],
+            ),
+            amount_a,
+        )?;
+
+        let escrow_b = &ctx.accounts.escrow_b;
+        let bump_b = [escrow_b.bump];
+        let seeds_b = &[b"otc_escrow", deal_key.as_ref(), escrow_b.owner.as_ref(), &bump_b[..]];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_b_token_account.to_account_info(),
+                    to: ctx.accounts.party_a_token_account.to_account_info(),
+                    authority: ctx.accounts.escrow_b.to_account_info(),
+                },
+                &[&seeds_b[..]],
+            ),
+            amount_b,
+        )?;
+
+        ctx.accounts.otc_deal.settled = true;
+
+        emit!(OtcSettledEvent {
+            deal: deal_key,
+            amount_a,
+            amount_b,
+        });
+
+        Ok(())
+    }
+
+    /// Returns whatever is left in an escrow to its owner. Open to each side
+    /// after settlement, or once the deal expires without a compatible match.
+    pub fn close_otc_escrow(ctx: Context<CloseOtcEscrow>) -> Result<()> {
+        let deal = &ctx.accounts.otc_deal;
+        let expired = Clock::get()?.slot > deal.expires_at_slot;
+        let unmatched = !deal.pending && !(deal.resolved && deal.compatible);
+        require!(deal.settled || (expired && unmatched), ErrorCode::OtcNotExpired);
+
+        let deal_key = deal.key();
+        let escrow = &ctx.accounts.otc_escrow;
+        let bump = [escrow.bump];
+        let seeds = &[b"otc_escrow", deal_key.as_ref(), escrow.owner.as_ref(), &bump[..]];
+        let signer = &[&seeds[..]];
+
+        let amount = ctx.accounts.escrow_token_account.amount;
+        if amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.escrow_token_account.to_account_info(),
+                        to: ctx.accounts.owner_token_account.to_account_info(),
+                        authority: ctx.accounts.otc_escrow.to_account_info(),
+                    },
+                    signer,
+                ),
+                amount,
+            )?;
+        }
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_token_account.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.otc_escrow.to_account_info(),
+            },
+            signer,
+        ))?;
+
+        Ok(())
+    }
+
+    pub fn init_open_balance_comp_def(ctx: Context<InitOpenBalanceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_credit_balance_comp_def(ctx: Context<InitCreditBalanceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_debit_balance_comp_def(ctx: Context<InitDebitBalanceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_cross_shielded_comp_def(ctx: Context<InitCrossShieldedCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Creates the signer's shielded balance for one mint, starting at an
+    /// encrypted zero, and the mint's vault if it doesn't exist yet.
+    pub fn open_shielded_balance(
+        ctx: Context<OpenShieldedBalance>,
+        computation_offset: u64,
+        state_nonce: u128,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.mint = ctx.accounts.mint.key();
+        vault.bump = ctx.bumps.vault;
+
+        let balance = &mut ctx.accounts.shielded_balance;
+        balance.owner = ctx.accounts.payer.key();
+        balance.mint = ctx.accounts.mint.key();
+        balance.pending = true;
+        balance.pending_since = Clock::get()?.slot;
+        balance.bump = ctx.bumps.shielded_balance;
+        let balance_key = balance.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            vec![Argument::PlaintextU128(state_nonce)],
+            None,
+            vec![OpenBalanceCallback::callback_ix(&[CallbackAccount {
+                pubkey: balance_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "open_balance", auto_serialize = false)]
+    pub fn open_balance_callback(
+        ctx: Context<OpenBalanceCallback>,
+        output: ComputationOutputs<EncryptedBalance>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let balance = &mut ctx.accounts.shielded_balance;
+        balance.encrypted_balance = state.ciphertexts;
+        balance.balance_nonce = state.nonce;
+        balance.pending = false;
+
+        Ok(())
+    }
+
+    /// Moves tokens into the vault and adds them to the signer's shielded
+    /// balance.
+    pub fn deposit_shielded(
+        ctx: Context<DepositShielded>,
+        computation_offset: u64,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOrderAmount);
+        require!(!ctx.accounts.shielded_balance.pending, ErrorCode::BalanceBusy);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer_token_account.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.payer.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let balance = &mut ctx.accounts.shielded_balance;
+        balance.pending = true;
+        balance.pending_since = Clock::get()?.slot;
+        balance.pending_deposit = amount;
+        let balance_key = balance.key();
+        let args = vec![
+            Argument::PlaintextU128(balance.balance_nonce),
+            Argument::Account(balance_key, 8, 32 * BALANCE_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(amount),
+        ];
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CreditBalanceCallback::callback_ix(&[CallbackAccount {
+                pubkey: balance_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "credit_balance", auto_serialize = false)]
+    pub fn credit_balance_callback(
+        ctx: Context<CreditBalanceCallback>,
+        output: ComputationOutputs<EncryptedBalance>,
+    ) -> Result<()> {
+        let balance = &mut ctx.accounts.shielded_balance;
+        // Already released by release_stuck_balance.
+        if !balance.pending {
+            return Ok(());
+        }
+        balance.pending = false;
+
+        match output {
+            ComputationOutputs::Success(state) => {
+                balance.encrypted_balance = state.ciphertexts;
+                balance.balance_nonce = state.nonce;
+            }
+            // The tokens are already in the vault; hand them back through
+            // claim_shielded_withdrawal rather than losing the deposit.
+            _ => balance.withdrawable += balance.pending_deposit,
+        }
+        balance.pending_deposit = 0;
+
+        Ok(())
+    }
+
+    /// Asks MPC to debit `amount` from the signer's shielded balance. If the
+    /// balance covers it, claim_shielded_withdrawal releases the tokens.
+    pub fn withdraw_shielded(
+        ctx: Context<WithdrawShielded>,
+        computation_offset: u64,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOrderAmount);
+
+        let balance = &mut ctx.accounts.shielded_balance;
+        require!(!balance.pending, ErrorCode::BalanceBusy);
+        require!(balance.withdrawable == 0, ErrorCode::WithdrawalUnclaimed);
+        balance.pending = true;
+        balance.pending_since = Clock::get()?.slot;
+        balance.requested_withdrawal = amount;
+        let balance_key = balance.key();
+        let args = vec![
+            Argument::PlaintextU128(balance.balance_nonce),
+            Argument::Account(balance_key, 8, 32 * BALANCE_CIPHERTEXTS as u32),
+            Argument::PlaintextU64(amount),
+        ];
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![DebitBalanceCallback::callback_ix(&[CallbackAccount {
+                pubkey: balance_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "debit_balance", auto_serialize = false)]
+    pub fn debit_balance_callback(
+        ctx: Context<DebitBalanceCallback>,
+        output: ComputationOutputs<DebitBalanceOutput>,
+    ) -> Result<()> {
+        let balance = &mut ctx.accounts.shielded_balance;
+        // Already released by release_stuck_balance.
+        if !balance.pending {
+            return Ok(());
+        }
+        balance.pending = false;
+
+        // A failed computation debits nothing, the same as a refused one.
+        let ok = match output {
+            ComputationOutputs::Success(DebitBalanceOutput { balance: state, ok }) => {
+                balance.encrypted_balance = state.ciphertexts;
+                balance.balance_nonce = state.nonce;
+                ok
+            }
+            _ => false,
+        };
+        if ok {
+            balance.withdrawable = balance.requested_withdrawal;
+        }
+        balance.requested_withdrawal = 0;
+
+        emit!(ShieldedWithdrawalEvent {
+            balance: balance.key(),
+            amount: balance.withdrawable,
+            ok,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_shielded_withdrawal(ctx: Context<ClaimShieldedWithdrawal>) -> Result<()> {
+        let amount = ctx.accounts.shielded_balance.withdrawable;
+        require!(amount > 0, ErrorCode::NothingToWithdraw);
+        ctx.accounts.shielded_balance.withdrawable = 0;
+
+        let vault = &ctx.accounts.vault;
+        let bump = [vault.bump];
+        let seeds = &[b"vault", vault.mint.as_ref(), &bump[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.vault.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Posts an order to trade between two of the signer's shielded
+    /// balances. Only the sizes are encrypted; the pair is public.
+    pub fn place_shielded_order(
+        ctx: Context<PlaceShieldedOrder>,
+        nonce: u64,
+        encrypted_amount_in: [u8; 32],
+        encrypted_amount_out_min: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.token_in_mint.key() != ctx.accounts.token_out_mint.key(),
+            ErrorCode::MintMismatch
+        );
+
+        let order = &mut ctx.accounts.shielded_order;
+        order.owner = ctx.accounts.owner.key();
+        order.token_in_mint = ctx.accounts.token_in_mint.key();
+        order.token_out_mint = ctx.accounts.token_out_mint.key();
+        order.nonce = nonce;
+        order.encrypted_amount_in = encrypted_amount_in;
+        order.encrypted_amount_out_min = encrypted_amount_out_min;
+        order.encryption_pubkey = encryption_pubkey;
+        order.encryption_nonce = encryption_nonce;
+        order.active = true;
+        order.bump = ctx.bumps.shielded_order;
+
+        Ok(())
+    }
+
+    /// Closes an order, including one whose cross never came back.
+    pub fn close_shielded_order(ctx: Context<CloseShieldedOrder>) -> Result<()> {
+        let order = &ctx.accounts.shielded_order;
+        require!(
+            !order.pending || Clock::get()?.slot > order.pending_since + SHIELDED_PENDING_TIMEOUT_SLOTS,
+            ErrorCode::BalanceBusy
+        );
+        Ok(())
+    }
+
+    /// Permissionless. Clears a shielded balance whose computation never came
+    /// back. An uncredited deposit becomes claimable through
+    /// claim_shielded_withdrawal; a withdrawal request is dropped.
+    pub fn release_stuck_balance(ctx: Context<ReleaseStuckBalance>) -> Result<()> {
+        let balance = &mut ctx.accounts.shielded_balance;
+        require!(balance.pending, ErrorCode::BalanceNotStuck);
+        require!(
+            Clock::get()?.slot > balance.pending_since + SHIELDED_PENDING_TIMEOUT_SLOTS,
+            ErrorCode::BalanceNotStuck
+        );
+
+        balance.pending = false;
+        balance.withdrawable += balance.pending_deposit;
+        balance.pending_deposit = 0;
+        balance.requested_withdrawal = 0;
+
+        Ok(())
+    }
+
+    /// Crosses two shielded orders entirely inside the vault. No tokens move
+    /// and no accounts are created; only the four balances are rewritten.
+    pub fn cross_shielded_orders(ctx: Context<CrossShieldedOrders>, computation_offset: u64) -> Result<()> {
+        let a = &ctx.accounts.order_a;
+        let b = &ctx.accounts.order_b;
+        require!(a.active && b.active, ErrorCode::SettlementNotActive);
+        require!(!a.pending && !b.pending, ErrorCode::BalanceBusy);
+        require_keys_neq!(a.owner, b.owner, ErrorCode::InvalidCounterparty);
+        require_keys_eq!(a.token_in_mint, b.token_out_mint, ErrorCode::MintMismatch);
+        require_keys_eq!(a.token_out_mint, b.token_in_mint, ErrorCode::MintMismatch);
+
+        let balances = [
+            &ctx.accounts.a_in_balance,
+            &ctx.accounts.a_out_balance,
+            &ctx.accounts.b_in_balance,
+            &ctx.accounts.b_out_balance,
+        ];
+        require!(balances.iter().all(|bal| !bal.pending), ErrorCode::BalanceBusy);
+
+        let mut args = Vec::with_capacity(16);
+        for order in [a, b] {
+            args.extend([
+                Argument::ArcisPubkey(order.encryption_pubkey),
+                Argument::PlaintextU128(order.encryption_nonce),
+                Argument::EncryptedU64(order.encrypted_amount_in),
+                Argument::EncryptedU64(order.encrypted_amount_out_min),
+            ]);
+        }
+        for bal in balances {
+            args.extend([
+                Argument::PlaintextU128(bal.balance_nonce),
+                Argument::Account(bal.key(), 8, 32 * BALANCE_CIPHERTEXTS as u32),
+            ]);
+        }
+
+        let callback_accounts: Vec<CallbackAccount> = [
+            a.key(),
+            b.key(),
+            ctx.accounts.a_in_balance.key(),
+            ctx.accounts.a_out_balance.key(),
+            ctx.accounts.b_in_balance.key(),
+            ctx.accounts.b_out_balance.key(),
+        ]
+        .into_iter()
+        .map(|pubkey| CallbackAccount { pubkey, is_writable: true })
+        .collect();
+
+        let now = Clock::get()?.slot;
+        for order in [&mut ctx.accounts.order_a, &mut ctx.accounts.order_b] {
+            order.pending = true;
+            order.pending_since = now;
+        }
+        for balance in [
+            &mut ctx.accounts.a_in_balance,
+            &mut ctx.accounts.a_out_balance,
+            &mut ctx.accounts.b_in_balance,
+            &mut ctx.accounts.b_out_balance,
+        ] {
+            balance.pending = true;
+            balance.pending_since = now;
+        }
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CrossShieldedCallback::callback_ix(&callback_accounts)],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cross_shielded", auto_serialize = false)]
+    pub fn cross_shielded_callback(
+        ctx: Context<CrossShieldedCallback>,
+        output: ComputationOutputs<CrossShieldedOutput>,
+    ) -> Result<()> {
+        let accounts = &mut ctx.accounts;
+        // If any balance was released while this was in flight, writing the
+        // rest would leave the four out of step; drop the output whole.
+        let live = [
+            &accounts.a_in_balance,
+            &accounts.a_out_balance,
+            &accounts.b_in_balance,
+            &accounts.b_out_balance,
+        ]
+        .iter()
+        .all(|balance| balance.pending);
+
+        let crossed = match output {
+            ComputationOutputs::Success(o) if live => {
+                for (balance, state) in [
+                    (&mut accounts.a_in_balance, o.a_in),
+                    (&mut accounts.a_out_balance, o.a_out),
+                    (&mut accounts.b_in_balance, o.b_in),
+                    (&mut accounts.b_out_balance, o.b_out),
+                ] {
+                    balance.encrypted_balance = state.ciphertexts;
+                    balance.balance_nonce = state.nonce;
+                }
+                o.crossed
+            }
+            _ => false,
+        };
+
+        for balance in [
+            &mut accounts.a_in_balance,
+            &mut accounts.a_out_balance,
+            &mut accounts.b_in_balance,
+            &mut accounts.b_out_balance,
+        ] {
+            balance.pending = false;
+        }
+
+        // An order gets one attempt. A crossed order is spent, and one that
+        // didn't cross has to be placed again, so nobody can keep its
+        // owner's balances pending by queueing it over and over.
+        for order in [&mut accounts.order_a, &mut accounts.order_b] {
+            order.pending = false;
+            order.active = false;
+        }
+
+        emit!(ShieldedCrossEvent {
+            order_a: accounts.order_a.key(),
+            order_b: accounts.order_b.key(),
+            crossed,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_private_transfer_comp_def(ctx: Context<InitPrivateTransferCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Moves an encrypted amount from the signer's shielded balance to
+    /// another user's balance of the same mint. Only success is revealed.
+    pub fn private_transfer(
+        ctx: Context<PrivateTransfer>,
+        computation_offset: u64,
+        encrypted_amount: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        let from = &ctx.accounts.from_balance;
+        let to = &ctx.accounts.to_balance;
+        require!(!from.pending && !to.pending, ErrorCode::BalanceBusy);
+        require_keys_neq!(from.owner, to.owner, ErrorCode::InvalidCounterparty);
+
+        let from_key = from.key();
+        let to_key = to.key();
+        let args = vec![
+            Argument::ArcisPubkey(encryption_pubkey),
+            Argument::PlaintextU128(encryption_nonce),
+            Argument::EncryptedU64(encrypted_amount),
+            Argument::PlaintextU128(from.balance_nonce),
+            Argument::Account(from_key, 8, 32 * BALANCE_CIPHERTEXTS as u32),
+            Argument::PlaintextU128(to.balance_nonce),
+            Argument::Account(to_key, 8, 32 * BALANCE_CIPHERTEXTS as u32),
+        ];
+
+        let now = Clock::get()?.slot;
+        for balance in [&mut ctx.accounts.from_balance, &mut ctx.accounts.to_balance] {
+            balance.pending = true;
+            balance.pending_since = now;
+        }
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![PrivateTransferCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: from_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: to_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "private_transfer", auto_serialize = false)]
+    pub fn private_transfer_callback(
+        ctx: Context<PrivateTransferCallback>,
+        output: ComputationOutputs<PrivateTransferOutput>,
+    ) -> Result<()> {
+        let accounts = &mut ctx.accounts;
+        // As with cross_shielded_callback, a released side makes the whole
+        // output stale.
+        let live = accounts.from_balance.pending && accounts.to_balance.pending;
+
+        let ok = match output {
+            ComputationOutputs::Success(o) if live => {
+                for (balance, state) in [
+                    (&mut accounts.from_balance, o.from),
+                    (&mut accounts.to_balance, o.to),
+                ] {
+                    balance.encrypted_balance = state.ciphertexts;
+                    balance.balance_nonce = state.nonce;
+                }
+                o.ok
+            }
+            _ => false,
+        };
+
+        accounts.from_balance.pending = false;
+        accounts.to_balance.pending = false;
+
+        emit!(PrivateTransferEvent {
+            from: accounts.from_balance.key(),
+            to: accounts.to_balance.key(),
+            ok,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_compute_fee_comp_def(ctx: Context<InitComputeFeeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Creates the protocol fee config. Only the program's upgrade authority
+    /// may call it, and becomes the authority that sets per-mint schedules;
+    /// `treasury` owns the fee ATAs.
+    pub fn init_fee_config(ctx: Context<InitFeeConfig>, treasury: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.fee_config;
+        config.authority = ctx.accounts.authority.key();
+        config.treasury = treasury;
+        config.bump = ctx.bumps.fee_config;
+        Ok(())
+    }
+
+    /// Sets the tiered fee for swaps paying out in `mint`. `thresholds` are
+    /// the trailing volumes, in the mint's atoms, at which tiers 1..3 start.
+    pub fn set_fee_schedule(
+        ctx: Context<SetFeeSchedule>,
+        thresholds: [u64; FEE_TIERS - 1],
+        bps: [u16; FEE_TIERS],
+    ) -> Result<()> {
+        require!(thresholds.windows(2).all(|w| w[0] <= w[1]), ErrorCode::InvalidFeeSchedule);
+        require!(bps.iter().all(|&b| b <= MAX_FEE_BPS), ErrorCode::InvalidFeeSchedule);
+
+        let schedule = &mut ctx.accounts.fee_schedule;
+        schedule.mint = ctx.accounts.mint.key();
+        schedule.treasury_token_account = ctx.accounts.treasury_token_account.key();
+        schedule.thresholds = thresholds;
+        schedule.bps = bps;
+        schedule.bump = ctx.bumps.fee_schedule;
+        Ok(())
+    }
+
+    /// Charges the fee on output that has landed in the TempWallet since the
+    /// last assessment. The tier comes from the user's encrypted trailing
+    /// volume in that mint, which is updated in the same computation.
+    pub fn assess_fee(ctx: Context<AssessFee>, computation_offset: u64) -> Result<()> {
+        let amount = ctx.accounts.temp_token_account_out.amount;
+        let temp = &mut ctx.accounts.temp_wallet;
+        require!(!temp.fee_pending, ErrorCode::FeeBusy);
+        require!(amount > temp.fee_base, ErrorCode::NothingToAssess);
+        let increment = amount - temp.fee_base;
+        temp.fee_base = amount;
+        temp.fee_pending = true;
+        let temp_key = temp.key();
+
+        let epoch = Clock::get()?.slot / FEE_EPOCH_SLOTS;
+        let volume = &mut ctx.accounts.trader_volume;
+        require!(!volume.pending, ErrorCode::FeeBusy);
+        let fresh = !volume.initialized;
+        if fresh {
+            volume.user = temp.user;
+            volume.mint = temp.token_out_mint;
+            volume.bump = ctx.bumps.trader_volume;
+        }
+        let epochs_elapsed = epoch.saturating_sub(volume.epoch);
+        volume.epoch = epoch;
+        volume.pending = true;
+        let volume_key = volume.key();
+
+        let schedule = &ctx.accounts.fee_schedule;
+        let mut args = vec![
+            Argument::PlaintextU128(volume.volume_nonce),
+            Argument::Account(volume_key, 8, 32 * VOLUME_CIPHERTEXTS as u32),
+            Argument::PlaintextBool(fresh),
+            Argument::PlaintextU64(epochs_elapsed),
+            Argument::PlaintextU64(increment),
+        ];
+        args.extend(schedule.thresholds.map(Argument::PlaintextU64));
+        args.extend(schedule.bps.map(|b| Argument::PlaintextU64(b as u64)));
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ComputeFeeCallback::callback_ix(&[
+                CallbackAccount {
+                    pubkey: temp_key,
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: volume_key,
+                    is_writable: true,
+                },
+            ])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "compute_fee", auto_serialize = false)]
+    pub fn compute_fee_callback(
+        ctx: Context<ComputeFeeCallback>,
+        output: ComputationOutputs<ComputeFeeOutput>,
+    ) -> Result<()> {
+        let ComputeFeeOutput { volume: state, fee } = match output {
+            ComputationOutputs::Success(out) => out,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let volume = &mut ctx.accounts.trader_volume;
+        volume.encrypted_volume = state.ciphertexts;
+        volume.volume_nonce = state.nonce;
+        volume.initialized = true;
+        volume.pending = false;
+
+        let temp = &mut ctx.accounts.temp_wallet;
+        temp.fee_due = temp.fee_due.saturating_add(fee);
+        temp.fee_pending = false;
+
+        emit!(FeeAssessedEvent {
+            nonce: temp.nonce,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    /// Registers the compliance auditor's x25519 key and turns disclosure
+    /// on. Only the program's upgrade authority may call it, and becomes the
+    /// authority that may change it.
+    pub fn init_audit_config(ctx: Context<InitAuditConfig>, auditor_key: [u8; 32]) -> Result<()> {
+        require!(auditor_key != [0; 32], ErrorCode::InvalidAuditorKey);
+        let config = &mut ctx.accounts.audit_config;
+        config.authority = ctx.accounts.authority.key();
+        config.auditor_key = auditor_key;
+        config.enabled = true;
+        config.bump = ctx.bumps.audit_config;
+        Ok(())
+    }
+
+    /// Rotates the auditor key or switches disclosure on and off. Orders
+    /// already queued keep the key they were queued with.
+    pub fn set_auditor(ctx: Context<SetAuditor>, auditor_key: [u8; 32], enabled: bool) -> Result<()> {
+        require!(auditor_key != [0; 32], ErrorCode::InvalidAuditorKey);
+        let config = &mut ctx.accounts.audit_config;
+        config.auditor_key = auditor_key;
+        config.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn init_store_policy_comp_def(ctx: Context<InitStorePolicyCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_check_access_comp_def(ctx: Context<InitCheckAccessCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    /// Creates or replaces the signer's access policy. The policy arrives
+    /// encrypted to the authority's key and is moved into MXE state, where
+    /// only check_access reads it. `attester` is the key allowed to issue
+    /// attestations against it.
+    pub fn set_access_policy(
+        ctx: Context<SetAccessPolicy>,
+        computation_offset: u64,
+        attester: Pubkey,
+        encrypted_jurisdictions: [[u8; 32]; POLICY_JURISDICTIONS],
+        encrypted_min_tier: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        let policy = &mut ctx.accounts.access_policy;
+        require!(!policy.pending, ErrorCode::PolicyBusy);
+        policy.authority = ctx.accounts.authority.key();
+        policy.attester = attester;
+        policy.pending = true;
+        policy.bump = ctx.bumps.access_policy;
+        let policy_key = policy.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let mut args = vec![
+            Argument::ArcisPubkey(encryption_pubkey),
+            Argument::PlaintextU128(encryption_nonce),
+        ];
+        args.extend(encrypted_jurisdictions.iter().map(|c| Argument::EncryptedU64(*c)));
+        args.push(Argument::EncryptedU64(encrypted_min_tier));
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![StorePolicyCallback::callback_ix(&[CallbackAccount {
+                pubkey: policy_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "store_policy", auto_serialize = false)]
+    pub fn store_policy_callback(
+        ctx: Context<StorePolicyCallback>,
+        output: ComputationOutputs<EncryptedAccessPolicy>,
+    ) -> Result<()> {
+        let state = match output {
+            ComputationOutputs::Success(state) => state,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let policy = &mut ctx.accounts.access_policy;
+        policy.encrypted_policy = state.ciphertexts;
+        policy.state_nonce = state.nonce;
+        policy.initialized = true;
+        policy.pending = false;
+
+        Ok(())
+    }
+
+    /// Creates the still-open PoolPolicy for the pair `mint_a`/`mint_b` and
+    /// names the `authority` that may gate it. Only the program's upgrade
+    /// authority may create pools.
+    pub fn init_pool_policy(ctx: Context<InitPoolPolicy>, authority: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.mint_a.key() < ctx.accounts.mint_b.key(),
+            ErrorCode::UnorderedPoolMints
+        );
+        let pool = &mut ctx.accounts.pool_policy;
+        pool.authority = authority;
+        pool.mint_a = ctx.accounts.mint_a.key();
+        pool.mint_b = ctx.accounts.mint_b.key();
+        pool.access_policy = Pubkey::default();
+        pool.next_access_policy = Pubkey::default();
+        pool.effective_slot = 0;
+        pool.bump = ctx.bumps.pool_policy;
+        Ok(())
+    }
+
+    /// Binds the pair to `access_policy`, so every order placed into it must
+    /// first pass request_access against that policy. The change takes
+    /// effect POOL_POLICY_NOTICE_SLOTS from now; until then the current
+    /// policy keeps applying. Pass the default key to open the pair again.
+    pub fn set_pool_policy(ctx: Context<SetPoolPolicy>, access_policy: Pubkey) -> Result<()> {
+        let now = Clock::get()?.slot;
+        let pool = &mut ctx.accounts.pool_policy;
+        pool.access_policy = pool.active_policy(now);
+        pool.next_access_policy = access_policy;
+        pool.effective_slot = now + POOL_POLICY_NOTICE_SLOTS;
+        Ok(())
+    }
+
+    /// Stores `user`'s attestation, encrypted by the attester. Reissuing
+    /// replaces the previous one.
+    pub fn issue_attestation(
+        ctx: Context<IssueAttestation>,
+        encrypted_jurisdiction: [u8; 32],
+        encrypted_tier: [u8; 32],
+        encryption_pubkey: [u8; 32],
+        encryption_nonce: u128,
+    ) -> Result<()> {
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.policy = ctx.accounts.access_policy.key();
+        attestation.user = ctx.accounts.user.key();
+        attestation.encryption_key = encryption_pubkey;
+        attestation.encryption_nonce = encryption_nonce;
+        attestation.ciphertexts = [encrypted_jurisdiction, encrypted_tier];
+        attestation.bump = ctx.bumps.attestation;
+        Ok(())
+    }
+
+    /// Asks to have a TempWallet admitted under `access_policy` before it is
+    /// funded. fund_and_place_order refuses the order until the check passes,
+    /// so match_order never runs for a denied user.
+    pub fn request_access(ctx: Context<RequestAccess>, computation_offset: u64) -> Result<()> {
+        let policy = &ctx.accounts.access_policy;
+        require!(policy.initialized && !policy.pending, ErrorCode::PolicyNotSet);
+
+        let temp = &mut ctx.accounts.temp_wallet;
+        require!(!temp.is_funded, ErrorCode::AlreadyFunded);
+        require!(!temp.access_pending, ErrorCode::AccessCheckPending);
+        temp.access_policy = policy.key();
+        temp.access_granted = false;
+        temp.access_pending = true;
+        let temp_key = temp.key();
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let attestation = &ctx.accounts.attestation;
+        let policy_key = policy.key();
+        let args = vec![
+            Argument::ArcisPubkey(attestation.encryption_key),
+            Argument::PlaintextU128(attestation.encryption_nonce),
+            Argument::EncryptedU64(attestation.ciphertexts[0]),
+            Argument::EncryptedU64(attestation.ciphertexts[1]),
+            Argument::PlaintextU128(policy.state_nonce),
+            Argument::Account(policy_key, 8, 32 * ACCESS_POLICY_CIPHERTEXTS as u32),
+        ];
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CheckAccessCallback::callback_ix(&[CallbackAccount {
+                pubkey: temp_key,
+                is_writable: true,
+            }])],
+        )?;
+
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "check_access", auto_serialize = false)]
+    pub fn check_access_callback(
+        ctx: Context<CheckAccessCallback>,
+        output: ComputationOutputs<AccessDecision>,
+    ) -> Result<()> {
+        let decision = match output {
+            ComputationOutputs::Success(decision) => decision,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let temp = &mut ctx.accounts.temp_wallet;
+        temp.access_granted = decision.allowed;
+        temp.access_pending = false;
+
+        emit!(AccessCheckedEvent {
+            temp_wallet: temp.key(),
+            access_policy: temp.access_policy,
+            allowed: decision.allowed,
+        });
+
+        Ok(())
+    }
+}
+
+/// Arguments for an `Enc<Shared, SwapOrder>` circuit input, built from the
+/// ciphertexts and key material stored on a TempWallet.
+fn swap_order_args(temp: &TempWallet) -> Vec<Argument> {
+    vec![
+        Argument::ArcisPubkey(temp.encryption_pubkey),
+        Argument::PlaintextU128(temp.encryption_nonce),
+        Argument::EncryptedU64(temp.encrypted_amount_in),
+        Argument::EncryptedU64(temp.encrypted_amount_out_min),
+        Argument::EncryptedU128(temp.encrypted_token_in[0]),
+        Argument::EncryptedU128(temp.encrypted_token_in[1]),
+        Argument::EncryptedU128(temp.encrypted_token_out[0]),
+        Argument::EncryptedU128(temp.encrypted_token_out[1]),
+        Argument::EncryptedU64(temp.encrypted_nonce),
+        Argument::EncryptedU64(temp.encrypted_limit_price),
+        Argument::EncryptedU64(temp.encrypted_stop_price),
+        Argument::EncryptedU64(temp.encrypted_take_profit_price),
+        Argument::EncryptedU64(temp.encrypted_display_size),
+        Argument::EncryptedU64(temp.encrypted_expires_at_slot),
+    ]
+}
+
+/// Arguments for match_order: the encrypted order followed by the
+/// TempWallet's public view of it, its revision, the slot its result must land
+/// by, the key its disclosure copy is encrypted to, and the MXE state nonce.
+fn match_order_args(
+    temp: &TempWallet,
+    deadline_slot: u64,
+    disclosure_key: [u8; 32],
+    disclosure_nonce: u128,
+    state_nonce: u128,
+) -> Vec<Argument> {
+    let [token_in_lo, token_in_hi] = mint_limbs(&temp.token_in_mint);
+    let [token_out_lo, token_out_hi] = mint_limbs(&temp.token_out_mint);
+    let mut args = swap_order_args(temp);
+    args.extend([
+        Argument::PlaintextU64(temp.nonce),
+        Argument::PlaintextU64(temp.amount_in),
+        Argument::PlaintextU128(token_in_lo),
+        Argument::PlaintextU128(token_in_hi),
+        Argument::PlaintextU128(token_out_lo),
+        Argument::PlaintextU128(token_out_hi),
+        Argument::PlaintextBool(temp.order_type == OrderType::Iceberg),
+        Argument::PlaintextU8(temp.revision),
+        Argument::PlaintextU64(deadline_slot),
+        Argument::ArcisPubkey(disclosure_key),
+        Argument::PlaintextU128(disclosure_nonce),
+        Argument::PlaintextU128(state_nonce),
+    ]);
+    args
+}
+
+/// CPI into the Jupiter route in `data`, passing the remaining accounts
+/// through and signing as the TempWallet that holds the tokens.
+fn invoke_jupiter<'info>(
+    jupiter_program: Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    data: Vec<u8>,
+    temp: &TempWallet,
+) -> Result<()> {
+    let accounts = remaining_accounts
+        .iter()
+        .map(|acc| AccountMeta {
+            pubkey: *acc.key,
+            is_signer: acc.is_signer,
+            is_writable: acc.is_writable,
+        })
+        .collect();
+
+    let ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: jupiter_program,
+        accounts,
+        data,
+    };
+
+    let bump_arr = [temp.bump];
+    let nonce_bytes = temp.nonce.to_le_bytes();
+    let seeds = &[
+        b"temp_wallet",
+        temp.user.as_ref(),
+        nonce_bytes.as_ref(),
+        &bump_arr[..],
+    ];
+    anchor_lang::solana_program::program::invoke_signed(&ix, remaining_accounts, &[&seeds[..]])?;
+    Ok(())
+}
+
+/// Orders a pair's mints the way PoolPolicy seeds expect them.
+fn pool_mints(a: Pubkey, b: Pubkey) -> (Pubkey, Pubkey) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Refuses `temp` unless it was admitted under the access policy bound to
+/// its pair. Pairs without a PoolPolicy are open to everyone.
+fn require_pool_access(pool_policy: &AccountInfo, temp: &TempWallet, program_id: &Pubkey) -> Result<()> {
+    let (mint_a, mint_b) = pool_mints(temp.token_in_mint, temp.token_out_mint);
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[b"pool_policy", mint_a.as_ref(), mint_b.as_ref()],
+        program_id,
+    );
+    require_keys_eq!(pool_policy.key(), pda, ErrorCode::InvalidPoolPolicy);
+    if pool_policy.data_is_empty() {
+        return Ok(());
+    }
+
+    let data = pool_policy.try_borrow_data()?;
+    let pool: PoolPolicy = AccountDeserialize::try_deserialize(&mut &data[..])?;
+    let access_policy = pool.active_policy(Clock::get()?.slot);
+    if access_policy == Pubkey::default() {
+        return Ok(());
+    }
+    require!(
+        temp.access_policy == access_policy && temp.access_granted && !temp.access_pending,
+        ErrorCode::AccessNotGranted
+    );
+    Ok(())
+}
+
+/// The auditor's x25519 key, if an AuditConfig exists with disclosure on.
+fn auditor_key(config: &AccountInfo) -> Result<Option<[u8; 32]>> {
+    if config.data_is_empty() {
+        return Ok(None);
+    }
+    let data = config.try_borrow_data()?;
+    let config: AuditConfig = AccountDeserialize::try_deserialize(&mut &data[..])?;
+    Ok(config.enabled.then_some(config.auditor_key))
+}
+
+/// Marks a batch whose computation failed or never came back. Its encrypted
+/// book can't be trusted to clear, so settle_batch_order refunds every deposit.
+fn abort_batch(batch: &mut Account<Batch>) {
+    batch.pending = false;
+    batch.aborted = true;
+    emit!(BatchAbortedEvent {
+        batch: batch.key(),
+        count: batch.count,
+    });
+}
+
+/// Whether a book_match fill stays inside both the taker's deposit and the
+/// maker's resting one.
+fn book_fill_fits(book: &OrderBook, deposit: u64, fill: &BookFill) -> bool {
+    if !fill.matched {
+        return true;
+    }
+    let slot = fill.slot as usize;
+    slot < BOOK_CAPACITY
+        && book.slot_status[slot] == BookSlotStatus::Resting
+        && fill.taker_gives <= deposit
+        && book.given[slot]
+            .checked_add(fill.taker_receives)
+            .is_some_and(|given| given <= book.deposited[slot])
+        && book.proceeds[slot].checked_add(fill.taker_gives).is_some()
+}
+
+/// Records a taker's fill so settle_book_match can pay it out.
+fn resolve_book_match(book: &Account<OrderBook>, book_match: &mut BookMatch, fill: BookFill) {
+    book_match.matched = fill.matched;
+    book_match.slot = fill.slot;
+    book_match.taker_gives = fill.taker_gives;
+    book_match.taker_receives = fill.taker_receives;
+    book_match.resolved = true;
+
+    emit!(BookMatchedEvent {
+        order_book: book.key(),
+        temp_wallet: book_match.temp_wallet,
+        matched: fill.matched,
+        slot: fill.slot,
+        taker_gives: fill.taker_gives,
+        taker_receives: fill.taker_receives,
+    });
+}
+
+/// Splits a mint into the two little-endian u128 limbs circuits use for token identity.
+fn mint_limbs(mint: &Pubkey) -> [u128; 2] {
+    let bytes = mint.to_bytes();
+    [
+        u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+        u128::from_le_bytes(bytes[16..].try_into().unwrap()),
+    ]
+}
+
+/// Reads the aggregate price from a legacy Pyth price account, refusing feeds
+/// that are not trading or were last published too long ago.
+fn read_pyth_price(price_feed: &AccountInfo, current_slot: u64) -> Result<u64> {
+    let data = price_feed.try_borrow_data()?;
+    require!(data.len() >= 240, ErrorCode::InvalidPriceFeed);
+
+    let read_u32 = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let read_u64 = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+    require!(read_u32(0) == PYTH_MAGIC, ErrorCode::InvalidPriceFeed);
+
+    let price = i64::from_le_bytes(data[208..216].try_into().unwrap());
+    let status = read_u32(224);
+    let pub_slot = read_u64(232);
+
+    require!(status == PYTH_STATUS_TRADING, ErrorCode::StaleOraclePrice);
+    require!(current_slot.saturating_sub(pub_slot) <= MAX_ORACLE_AGE_SLOTS, ErrorCode::StaleOraclePrice);
+    require!(price > 0, ErrorCode::InvalidPriceFeed);
+
+    Ok(price as u64)
+}
+
+/// Reads the feed price as quote atoms per base atom in PRICE_SCALE fixed
+/// point, the unit match_order_mid compares hidden limits in.
+fn read_pyth_mid_price(
+    price_feed: &AccountInfo,
+    current_slot: u64,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Result<u128> {
+    let price = read_pyth_price(price_feed, current_slot)? as u128;
+    let expo = {
+        let data = price_feed.try_borrow_data()?;
+        i32::from_le_bytes(data[20..24].try_into().unwrap())
+    };
+
+    let shift = expo + quote_decimals as i32 - base_decimals as i32 + PRICE_SCALE_DECIMALS;
+    let factor = 10u128
+        .checked_pow(shift.unsigned_abs())
+        .ok_or(ErrorCode::InvalidPriceFeed)?;
+    let mid = if shift >= 0 {
+        price.checked_mul(factor).ok_or(ErrorCode::InvalidPriceFeed)?
+    } else {
+        price / factor
+    };
+    require!(mid > 0, ErrorCode::InvalidPriceFeed);
+
+    Ok(mid)
+}
+
+fn mint_from_limbs(limbs: [u128; 2]) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(&limbs[0].to_le_bytes());
+    bytes[16..].copy_from_slice(&limbs[1].to_le_bytes());
+    Pubkey::new_from_array(bytes)
+}
+
+#[cfg(feature = "devnet")]
+#[derive(Accounts)]
+#[instruction(public_nonce: u64)]
+pub struct InitSettlementTest<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 96 + 16 + 8 + 32 + 32 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 1 + 1 + 8 + 1 + 1,
+        seeds = [b"settlement", public_nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub settlement_request: Account<'info, SettlementRequest>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(feature = "devnet")]
+#[derive(Accounts)]
+#[instruction(amount_in: u64, token_in: Pubkey, token_out: Pubkey, nonce: u64)]
+pub struct SimulateMatchOrder<'info> {
+    #[account(
+        mut,
+        seeds = [b"settlement", nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub settlement_request: Account<'info, SettlementRequest>,
+    pub payer: Signer<'info>,
+}
+
+#[callback_accounts("match_order")]
+#[derive(Accounts)]
+pub struct MatchOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Validated manually in instruction
+    pub settlement_request: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub order_receipt: Box<Account<'info, OrderReceipt>>,
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(mut)]
+    pub order_disclosure: Box<Account<'info, OrderDisclosure>>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSwap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Account<'info, SettlementRequest>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+    )]
+    pub temp_wallet: Account<'info, TempWallet>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Account<'info, TokenAccount>,
+
+    /// CHECK: Pinned to Jupiter; the CPI is signed by the TempWallet
+    #[account(address = JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+}
+
+// The iceberg state leads so refill_clip can read it at offset 8. Orders of
+// other types carry it too but never use it.
+#[account]
+pub struct SettlementRequest {
+    pub encrypted_iceberg: [[u8; 32]; ICEBERG_STATE_CIPHERTEXTS],
+    pub iceberg_nonce: u128,
+    pub amount_in: u64,
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    pub nonce: u64,
+    pub realized_out: u64,
+    pub spent_in: u64,
+    pub active: bool,
+    pub fill_status: FillStatus,
+    pub partial: bool,
+    pub expires_at_slot: u64,
+    pub awaiting_trigger: bool,
+    pub triggered: bool,
+    // Most a single execute_swap may spend; set for TWAP slices and iceberg
+    // clips.
+    pub slice_amount: u64,
+    // Set once record_pair_stats has counted this fill.
+    pub stats_recorded: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillStatus {
+    Pending,
+    AwaitingVerification,
+    Verified,
+    Failed,
+    Unwound,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossStatus {
+    Idle,
+    Pending,
+    Crossed,
+}
+
+
+#[init_computation_definition_accounts("match_order", payer)]
+#[derive(Accounts)]
+pub struct InitMatchOrderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount_in: u64, nonce: u64)]
+pub struct CreatePrivateSwap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 32 + 32 + 64 + 64 + 32 + 32 + 32 + 32 + 32 + 32 + 32 + 16 + 1 + 32 + 1 + 1 + 8 + 8 + 1 + 32 + 1 + 1 + 1 + 8 + 1,
+        seeds = [b"temp_wallet", payer.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub temp_wallet: Account<'info, TempWallet>,
+    pub token_in_mint: Account<'info, Mint>,
+    pub token_out_mint: Account<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("match_order", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct FundAndPlaceOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account: Box<Account<'info, TokenAccount>>,
+    
+    // Accounts for place_order logic
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 96 + 16 + 8 + 32 + 32 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 1 + 1 + 8 + 1 + 1,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderReceipt::SPACE,
+        seeds = [b"receipt", temp_wallet.key().as_ref()],
+        bump
+    )]
+    pub order_receipt: Box<Account<'info, OrderReceipt>>,
+    /// CHECK: May be uninitialized when no auditor has been registered
+    #[account(
+        seeds = [b"audit_config"],
+        bump,
+    )]
+    pub audit_config: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderDisclosure::SPACE,
+        seeds = [b"disclosure", temp_wallet.key().as_ref()],
+        bump
+    )]
+    pub order_disclosure: Box<Account<'info, OrderDisclosure>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"receipt", temp_wallet.key().as_ref()],
+        bump = order_receipt.bump,
+    )]
+    pub order_receipt: Box<Account<'info, OrderReceipt>>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"disclosure", temp_wallet.key().as_ref()],
+        bump = order_disclosure.bump,
+    )]
+    pub order_disclosure: Box<Account<'info, OrderDisclosure>>,
+}
+
+#[queue_computation_accounts("match_order", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct AmendOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"receipt", temp_wallet.key().as_ref()],
+        bump = order_receipt.bump,
+    )]
+    pub order_receipt: Box<Account<'info, OrderReceipt>>,
+    /// CHECK: May be uninitialized when no auditor has been registered
+    #[account(
+        seeds = [b"audit_config"],
+        bump,
+    )]
+    pub audit_config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"disclosure", temp_wallet.key().as_ref()],
+        bump = order_disclosure.bump,
+    )]
+    pub order_disclosure: Box<Account<'info, OrderDisclosure>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[derive(Accounts)]
+pub struct ReturnTokensToUser<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Account<'info, TempWallet>,
+    /// CHECK: Verified by has_one on temp_wallet
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = user,
+    )]
+    pub user_token_account_out: Account<'info, TokenAccount>,
+    /// CHECK: May be uninitialized when the mint has no fee schedule
+    #[account(
+        seeds = [b"fee_schedule", temp_wallet.token_out_mint.as_ref()],
+        bump,
+    )]
+    pub fee_schedule: UncheckedAccount<'info>,
+    /// CHECK: Checked against the fee schedule when a fee is due
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+    /// CHECK: May be uninitialized for orders settled without match_order
+    #[account(
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub settlement_request: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UnwindFailedFill<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Account<'info, SettlementRequest>,
+    #[account(
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Account<'info, TempWallet>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Account<'info, TokenAccount>,
+
+    /// CHECK: Pinned to Jupiter; the CPI is signed by the TempWallet
+    #[account(address = JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+}
+
+#[init_computation_definition_accounts("verify_fill", payer)]
+#[derive(Accounts)]
+pub struct InitVerifyFillCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("verify_fill", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct VerifyFill<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_VERIFY_FILL))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("verify_fill")]
+#[derive(Accounts)]
+pub struct VerifyFillCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_VERIFY_FILL)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Validated manually in instruction
+    pub settlement_request: UncheckedAccount<'info>,
+}
+
+#[init_computation_definition_accounts("cross_orders", payer)]
+#[derive(Accounts)]
+pub struct InitCrossOrdersCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("cross_orders", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct FundAndCrossOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    // Crossing commits both deposits, so both owners have to agree to it.
+    pub user_a: Signer<'info>,
+    pub user_b: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user_a.key().as_ref(), temp_wallet_a.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet_a.bump
+    )]
+    pub temp_wallet_a: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet_a.token_in_mint,
+        associated_token::authority = temp_wallet_a,
+    )]
+    pub temp_token_account_a: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user_b.key().as_ref(), temp_wallet_b.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet_b.bump
+    )]
+    pub temp_wallet_b: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet_b.token_in_mint,
+        associated_token::authority = temp_wallet_b,
+    )]
+    pub temp_token_account_b: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1,
+        seeds = [b"cross", temp_wallet_a.nonce.to_le_bytes().as_ref(), temp_wallet_b.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub cross_settlement: Box<Account<'info, CrossSettlement>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CROSS_ORDERS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("cross_orders")]
+#[derive(Accounts)]
+pub struct CrossOrdersCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CROSS_ORDERS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Validated manually in instruction
+    pub cross_settlement: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub temp_wallet_a: Box<Account<'info, TempWallet>>,
+    #[account(mut)]
+    pub temp_wallet_b: Box<Account<'info, TempWallet>>,
+}
+
+#[init_computation_definition_accounts("match_order_mid", payer)]
+#[derive(Accounts)]
+pub struct InitMatchOrderMidCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("match_order_mid", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct FundAndCrossAtMid<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub user_a: Signer<'info>,
+    pub user_b: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user_a.key().as_ref(), temp_wallet_a.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet_a.bump
+    )]
+    pub temp_wallet_a: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet_a.token_in_mint,
+        associated_token::authority = temp_wallet_a,
+    )]
+    pub temp_token_account_a: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user_b.key().as_ref(), temp_wallet_b.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet_b.bump
+    )]
+    pub temp_wallet_b: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet_b.token_in_mint,
+        associated_token::authority = temp_wallet_b,
+    )]
+    pub temp_token_account_b: Box<Account<'info, TokenAccount>>,
+    #[account(address = temp_wallet_a.token_in_mint)]
+    pub base_mint: Box<Account<'info, Mint>>,
+    #[account(address = temp_wallet_a.token_out_mint)]
+    pub quote_mint: Box<Account<'info, Mint>>,
+    /// CHECK: Pyth price account named by both orders, parsed in read_pyth_mid_price
+    #[account(address = temp_wallet_a.price_feed @ ErrorCode::InvalidPriceFeed)]
+    pub price_feed: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1,
+        seeds = [b"cross", temp_wallet_a.nonce.to_le_bytes().as_ref(), temp_wallet_b.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub cross_settlement: Box<Account<'info, CrossSettlement>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER_MID))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("match_order_mid")]
+#[derive(Accounts)]
+pub struct MatchOrderMidCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER_MID)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Validated manually in instruction
+    pub cross_settlement: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub temp_wallet_a: Box<Account<'info, TempWallet>>,
+    #[account(mut)]
+    pub temp_wallet_b: Box<Account<'info, TempWallet>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleCrossedOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"cross", cross_settlement.nonce_a.to_le_bytes().as_ref(), cross_settlement.nonce_b.to_le_bytes().as_ref()],
+        bump = cross_settlement.bump,
+    )]
+    pub cross_settlement: Box<Account<'info, CrossSettlement>>,
+    #[account(
+        mut,
+        address = cross_settlement.temp_wallet_a,
+    )]
+    pub temp_wallet_a: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        address = cross_settlement.temp_wallet_b,
+    )]
+    pub temp_wallet_b: Box<Account<'info, TempWallet>>,
+    #[account(address = temp_wallet_a.token_out_mint)]
+    pub token_a_out_mint: Box<Account<'info, Mint>>,
+    #[account(address = temp_wallet_b.token_out_mint)]
+    pub token_b_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet_a.token_in_mint,
+        associated_token::authority = temp_wallet_a,
+    )]
+    pub temp_token_account_a_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_a_out_mint,
+        associated_token::authority = temp_wallet_a,
+    )]
+    pub temp_token_account_a_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet_b.token_in_mint,
+        associated_token::authority = temp_wallet_b,
+    )]
+    pub temp_token_account_b_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_b_out_mint,
+        associated_token::authority = temp_wallet_b,
+    )]
+    pub temp_token_account_b_out: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundTokensToUser<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Account<'info, TempWallet>,
+
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = user,
+    )]
+    pub user_token_account_in: Account<'info, TokenAccount>,
+    /// CHECK: May be uninitialized for orders placed without match_order
+    #[account(
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub settlement_request: UncheckedAccount<'info>,
+    /// CHECK: May be uninitialized when the order never opened an RFQ
+    #[account(
+        seeds = [b"rfq", temp_wallet.key().as_ref()],
+        bump,
+    )]
+    pub rfq: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct CrossSettlement {
+    pub temp_wallet_a: Pubkey,
+    pub temp_wallet_b: Pubkey,
+    pub nonce_a: u64,
+    pub nonce_b: u64,
+    pub fill_a: u64,
+    pub fill_b: u64,
+    pub active: bool,
+    pub bump: u8,
+}
+
+#[init_computation_definition_accounts("open_batch", payer)]
+#[derive(Accounts)]
+pub struct InitOpenBatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("join_batch", payer)]
+#[derive(Accounts)]
+pub struct InitJoinBatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("clear_batch", payer)]
+#[derive(Accounts)]
+pub struct InitClearBatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("open_batch", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, epoch: u64)]
+pub struct OpenBatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub base_mint: Box<Account<'info, Mint>>,
+    pub quote_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Batch::SPACE,
+        seeds = [b"batch", base_mint.key().as_ref(), quote_mint.key().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch: Box<Account<'info, Batch>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = base_mint,
+        associated_token::authority = batch,
+    )]
+    pub batch_vault_base: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = quote_mint,
+        associated_token::authority = batch,
+    )]
+    pub batch_vault_quote: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_BATCH))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("open_batch")]
+#[derive(Accounts)]
+pub struct OpenBatchCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_BATCH)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub batch: Box<Account<'info, Batch>>,
+}
+
+#[queue_computation_accounts("join_batch", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct JoinBatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch.base_mint.as_ref(), batch.quote_mint.as_ref(), batch.epoch.to_le_bytes().as_ref()],
+        bump = batch.bump,
+    )]
+    pub batch: Box<Account<'info, Batch>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = batch,
+    )]
+    pub batch_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_JOIN_BATCH))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("join_batch")]
+#[derive(Accounts)]
+pub struct JoinBatchCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_JOIN_BATCH)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub batch: Box<Account<'info, Batch>>,
+}
+
+#[queue_computation_accounts("clear_batch", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ClearBatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch.base_mint.as_ref(), batch.quote_mint.as_ref(), batch.epoch.to_le_bytes().as_ref()],
+        bump = batch.bump,
+    )]
+    pub batch: Box<Account<'info, Batch>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CLEAR_BATCH))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("clear_batch")]
+#[derive(Accounts)]
+pub struct ClearBatchCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CLEAR_BATCH)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub batch: Box<Account<'info, Batch>>,
+}
+
+#[derive(Accounts)]
+pub struct AbortStuckBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"batch", batch.base_mint.as_ref(), batch.quote_mint.as_ref(), batch.epoch.to_le_bytes().as_ref()],
+        bump = batch.bump,
+    )]
+    pub batch: Box<Account<'info, Batch>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBatchOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch.base_mint.as_ref(), batch.quote_mint.as_ref(), batch.epoch.to_le_bytes().as_ref()],
+        bump = batch.bump,
+    )]
+    pub batch: Box<Account<'info, Batch>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(address = temp_wallet.token_out_mint)]
+    pub token_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = batch,
+    )]
+    pub batch_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = batch,
+    )]
+    pub batch_vault_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+// The encrypted book sits right after the discriminator so MPC jobs can read
+// it with a fixed `Argument::Account` offset.
+#[account]
+pub struct Batch {
+    pub encrypted_book: [[u8; 32]; BATCH_BOOK_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub epoch: u64,
+    pub count: u8,
+    pub pending: bool,
+    pub pending_since: u64,
+    pub cleared: bool,
+    pub aborted: bool,
+    pub clearing_price: u128,
+    pub temp_wallets: [Pubkey; BATCH_SIZE],
+    pub fills_in: [u64; BATCH_SIZE],
+    pub amounts_out: [u64; BATCH_SIZE],
+    pub settled: [bool; BATCH_SIZE],
+    pub bump: u8,
+}
+
+impl Batch {
+    pub const SPACE: usize = 32 * BATCH_BOOK_CIPHERTEXTS + 16 + 32 + 32 + 8 + 1 + 1 + 8 + 1 + 1 + 16
+        + 32 * BATCH_SIZE + 8 * BATCH_SIZE + 8 * BATCH_SIZE + BATCH_SIZE + 1;
+}
+
+#[init_computation_definition_accounts("shuffle_sequence", payer)]
+#[derive(Accounts)]
+pub struct InitShuffleSequenceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct OpenSequence<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub base_mint: Box<Account<'info, Mint>>,
+    pub quote_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + SequenceRound::SPACE,
+        seeds = [b"sequence", base_mint.key().as_ref(), quote_mint.key().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub sequence: Box<Account<'info, SequenceRound>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinSequence<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        mut,
+        seeds = [b"sequence", sequence.base_mint.as_ref(), sequence.quote_mint.as_ref(), sequence.epoch.to_le_bytes().as_ref()],
+        bump = sequence.bump,
+    )]
+    pub sequence: Box<Account<'info, SequenceRound>>,
+}
+
+#[queue_computation_accounts("shuffle_sequence", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ShuffleSequence<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"sequence", sequence.base_mint.as_ref(), sequence.quote_mint.as_ref(), sequence.epoch.to_le_bytes().as_ref()],
+        bump = sequence.bump,
+    )]
+    pub sequence: Box<Account<'info, SequenceRound>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SHUFFLE_SEQUENCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("shuffle_sequence")]
+#[derive(Accounts)]
+pub struct ShuffleSequenceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_SHUFFLE_SEQUENCE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub sequence: Box<Account<'info, SequenceRound>>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseSequenced<'info> {
+    #[account(
+        mut,
+        seeds = [b"sequence", sequence.base_mint.as_ref(), sequence.quote_mint.as_ref(), sequence.epoch.to_le_bytes().as_ref()],
+        bump = sequence.bump,
+    )]
+    pub sequence: Box<Account<'info, SequenceRound>>,
+    #[account(mut)]
+    /// CHECK: checked against the round's next slot; may have been closed by cancel_order.
+    pub settlement_request: UncheckedAccount<'info>,
+    /// CHECK: checked against the round's previous slot; may have been closed by cancel_order.
+    pub previous_settlement: UncheckedAccount<'info>,
+}
+
+// Settlements are listed in join order; `execution_order` holds the shuffled
+// slot indices and `cursor` how many of them have been released.
+#[account]
+pub struct SequenceRound {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub epoch: u64,
+    pub count: u8,
+    pub pending: bool,
+    pub shuffled: bool,
+    pub settlements: [Pubkey; BATCH_SIZE],
+    pub execution_order: [u8; BATCH_SIZE],
+    pub cursor: u8,
+    pub turn_started_slot: u64,
+    pub bump: u8,
+}
+
+impl SequenceRound {
+    pub const SPACE: usize = 32 + 32 + 8 + 1 + 1 + 1 + 32 * BATCH_SIZE + BATCH_SIZE + 1 + 8 + 1;
+}
+
+#[init_computation_definition_accounts("open_pair_stats", payer)]
+#[derive(Accounts)]
+pub struct InitOpenPairStatsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("record_pair_stats", payer)]
+#[derive(Accounts)]
+pub struct InitRecordPairStatsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("reveal_pair_stats", payer)]
+#[derive(Accounts)]
+pub struct InitRevealPairStatsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("open_pair_stats", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, epoch: u64)]
+pub struct OpenPairStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub base_mint: Box<Account<'info, Mint>>,
+    pub quote_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PairStats::SPACE,
+        seeds = [b"pair_stats", base_mint.key().as_ref(), quote_mint.key().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_PAIR_STATS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("open_pair_stats")]
+#[derive(Accounts)]
+pub struct OpenPairStatsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_PAIR_STATS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+}
+
+#[queue_computation_accounts("record_pair_stats", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RecordPairStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"pair_stats", pair_stats.base_mint.as_ref(), pair_stats.quote_mint.as_ref(), pair_stats.epoch.to_le_bytes().as_ref()],
+        bump = pair_stats.bump,
+    )]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_RECORD_PAIR_STATS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("record_pair_stats")]
+#[derive(Accounts)]
+pub struct RecordPairStatsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_RECORD_PAIR_STATS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+}
+
+#[queue_computation_accounts("reveal_pair_stats", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RevealPairStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"pair_stats", pair_stats.base_mint.as_ref(), pair_stats.quote_mint.as_ref(), pair_stats.epoch.to_le_bytes().as_ref()],
+        bump = pair_stats.bump,
+    )]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_PAIR_STATS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("reveal_pair_stats")]
+#[derive(Accounts)]
+pub struct RevealPairStatsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_PAIR_STATS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub pair_stats: Box<Account<'info, PairStats>>,
+}
+
+// The encrypted totals lead so MPC jobs can read them at offset 8. The bands
+// are filled in once the epoch has been revealed.
+#[account]
+pub struct PairStats {
+    pub encrypted_stats: [[u8; 32]; PAIR_STATS_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub epoch: u64,
+    pub pending: bool,
+    pub revealed: bool,
+    pub base_volume_band: u8,
+    pub quote_volume_band: u8,
+    pub order_count_band: u8,
+    pub bump: u8,
+}
+
+impl PairStats {
+    pub const SPACE: usize = 32 * PAIR_STATS_CIPHERTEXTS + 16 + 32 + 32 + 8 + 1 + 1 + 1 + 1 + 1 + 1;
+}
+
+#[init_computation_definition_accounts("book_open", payer)]
+#[derive(Accounts)]
+pub struct InitBookOpenCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("book_insert", payer)]
+#[derive(Accounts)]
+pub struct InitBookInsertCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("book_cancel", payer)]
+#[derive(Accounts)]
+pub struct InitBookCancelCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("book_match", payer)]
+#[derive(Accounts)]
+pub struct InitBookMatchCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("book_open", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct OpenOrderBook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub base_mint: Box<Account<'info, Mint>>,
+    pub quote_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderBook::SPACE,
+        seeds = [b"order_book", base_mint.key().as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = base_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_base: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = quote_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_quote: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_OPEN))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("book_open")]
+#[derive(Accounts)]
+pub struct BookOpenCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_OPEN)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub order_book: Box<Account<'info, OrderBook>>,
+}
+
+#[queue_computation_accounts("book_insert", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RestOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_INSERT))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("book_insert")]
+#[derive(Accounts)]
+pub struct BookInsertCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_INSERT)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub order_book: Box<Account<'info, OrderBook>>,
+}
+
+#[queue_computation_accounts("book_cancel", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CancelRestingOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_CANCEL))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("book_cancel")]
+#[derive(Accounts)]
+pub struct BookCancelCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_CANCEL)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub order_book: Box<Account<'info, OrderBook>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawRestingOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(address = temp_wallet.token_out_mint)]
+    pub token_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("book_match", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct MatchAgainstBook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BookMatch::SPACE,
+        seeds = [b"book_match", temp_wallet.key().as_ref()],
+        bump
+    )]
+    pub book_match: Box<Account<'info, BookMatch>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_MATCH))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("book_match")]
+#[derive(Accounts)]
+pub struct BookMatchCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_BOOK_MATCH)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(mut)]
+    pub book_match: Box<Account<'info, BookMatch>>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseStuckBook<'info> {
+    #[account(
+        mut,
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    /// CHECK: The BookMatch a stuck match_against_book was resolving, checked
+    /// against order_book.pending_match; ignored for other operations
+    #[account(mut)]
+    pub book_match: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBookMatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"order_book", order_book.base_mint.as_ref(), order_book.quote_mint.as_ref()],
+        bump = order_book.bump,
+    )]
+    pub order_book: Box<Account<'info, OrderBook>>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"book_match", temp_wallet.key().as_ref()],
+        bump = book_match.bump,
+        has_one = order_book @ ErrorCode::InvalidBookMatch,
+    )]
+    pub book_match: Box<Account<'info, BookMatch>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(address = temp_wallet.token_out_mint)]
+    pub token_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = order_book,
+    )]
+    pub book_vault_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+// Same layout rule as Batch: the encrypted book leads so MPC jobs can read
+// it at offset 8. Token balances per slot are tracked in plaintext since
+// every transfer in and out of the vaults is public anyway.
+#[account]
+pub struct OrderBook {
+    pub encrypted_book: [[u8; 32]; BOOK_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub pending: bool,
+    pub pending_op: BookOp,
+    pub pending_since: u64,
+    pub pending_slot: u8,
+    pub pending_match: Pubkey,
+    pub slots: [Pubkey; BOOK_CAPACITY],
+    pub slot_status: [BookSlotStatus; BOOK_CAPACITY],
+    pub deposited: [u64; BOOK_CAPACITY],
+    pub given: [u64; BOOK_CAPACITY],
+    pub proceeds: [u64; BOOK_CAPACITY],
+    pub bump: u8,
+}
+
+impl OrderBook {
+    pub const SPACE: usize = 32 * BOOK_CIPHERTEXTS + 16 + 32 + 32 + 1 + 1 + 8 + 1 + 32 + 32 * BOOK_CAPACITY
+        + BOOK_CAPACITY + 8 * BOOK_CAPACITY + 8 * BOOK_CAPACITY + 8 * BOOK_CAPACITY + 1;
+
+    pub fn trades_pair(&self, token_in: Pubkey, token_out: Pubkey) -> bool {
+        (token_in == self.base_mint && token_out == self.quote_mint)
+            || (token_in == self.quote_mint && token_out == self.base_mint)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookSlotStatus {
+    Empty,
+    Resting,
+    Closed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookOp {
+    Open,
+    Insert,
+    Cancel,
+    Match,
+}
+
+#[account]
+pub struct BookMatch {
+    pub order_book: Pubkey,
+    pub temp_wallet: Pubkey,
+    pub deposit: u64,
+    pub resolved: bool,
+    pub matched: bool,
+    pub slot: u8,
+    pub taker_gives: u64,
+    pub taker_receives: u64,
+    pub bump: u8,
+}
+
+impl BookMatch {
+    pub const SPACE: usize = 32 + 32 + 8 + 1 + 1 + 1 + 8 + 8 + 1;
+}
+
+#[init_computation_definition_accounts("open_remainder", payer)]
+#[derive(Accounts)]
+pub struct InitOpenRemainderCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("fill_round", payer)]
+#[derive(Accounts)]
+pub struct InitFillRoundCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("open_remainder", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct StartPartialFill<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PartialFill::SPACE,
+        seeds = [b"partial_fill", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub partial_fill: Box<Account<'info, PartialFill>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_REMAINDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("open_remainder")]
+#[derive(Accounts)]
+pub struct OpenRemainderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_REMAINDER)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub partial_fill: Box<Account<'info, PartialFill>>,
+    #[account(mut)]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+}
+
+#[queue_computation_accounts("fill_round", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct VerifyPartialFill<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"partial_fill", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = partial_fill.bump,
+    )]
+    pub partial_fill: Box<Account<'info, PartialFill>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_FILL_ROUND))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("fill_round")]
+#[derive(Accounts)]
+pub struct FillRoundCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_FILL_ROUND)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub partial_fill: Box<Account<'info, PartialFill>>,
+    #[account(mut)]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+}
+
+// The encrypted remainder leads so fill_round can read it at offset 8.
+#[account]
+pub struct PartialFill {
+    pub encrypted_remainder: [[u8; 32]; REMAINDER_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub nonce: u64,
+    pub rounds: u32,
+    pub bump: u8,
+}
+
+impl PartialFill {
+    pub const SPACE: usize = 32 * REMAINDER_CIPHERTEXTS + 16 + 8 + 4 + 1;
+}
+
+#[init_computation_definition_accounts("check_trigger", payer)]
+#[derive(Accounts)]
+pub struct InitCheckTriggerCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("check_trigger", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CheckTrigger<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(address = temp_wallet.price_feed @ ErrorCode::InvalidPriceFeed)]
+    /// CHECK: Pyth price account, parsed by read_pyth_price
+    pub price_feed: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_TRIGGER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("check_trigger")]
+#[derive(Accounts)]
+pub struct CheckTriggerCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_TRIGGER)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+}
+
+#[init_computation_definition_accounts("open_twap", payer)]
+#[derive(Accounts)]
+pub struct InitOpenTwapCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("next_slice", payer)]
+#[derive(Accounts)]
+pub struct InitNextSliceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("open_twap", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct StartTwap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TwapSchedule::SPACE,
+        seeds = [b"twap", temp_wallet.key().as_ref()],
+        bump
+    )]
+    pub twap_schedule: Box<Account<'info, TwapSchedule>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_TWAP))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("open_twap")]
+#[derive(Accounts)]
+pub struct OpenTwapCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_TWAP)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub twap_schedule: Box<Account<'info, TwapSchedule>>,
+}
+
+#[queue_computation_accounts("next_slice", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ReleaseTwapSlice<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        mut,
+        seeds = [b"twap", temp_wallet.key().as_ref()],
+        bump = twap_schedule.bump,
+    )]
+    pub twap_schedule: Box<Account<'info, TwapSchedule>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_NEXT_SLICE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("next_slice")]
+#[derive(Accounts)]
+pub struct NextSliceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_NEXT_SLICE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub twap_schedule: Box<Account<'info, TwapSchedule>>,
+    #[account(mut)]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+}
+
+#[init_computation_definition_accounts("refill_clip", payer)]
+#[derive(Accounts)]
+pub struct InitRefillClipCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("refill_clip", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RefillClip<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"settlement", settlement_request.nonce.to_le_bytes().as_ref()],
+        bump = settlement_request.bump,
+    )]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        constraint = temp_wallet.nonce == settlement_request.nonce @ ErrorCode::InvalidSettlementPDA,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REFILL_CLIP))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("refill_clip")]
+#[derive(Accounts)]
+pub struct RefillClipCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_REFILL_CLIP)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub settlement_request: Box<Account<'info, SettlementRequest>>,
+}
+
+#[init_computation_definition_accounts("select_quote", payer)]
+#[derive(Accounts)]
+pub struct InitSelectQuoteCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterMarketMaker<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MakerEscrow::SPACE,
+        seeds = [b"maker_escrow", authority.key().as_ref()],
+        bump,
+    )]
+    pub maker_escrow: Account<'info, MakerEscrow>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawMakerInventory<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"maker_escrow", authority.key().as_ref()],
+        bump = maker_escrow.bump,
+        has_one = authority,
+    )]
+    pub maker_escrow: Account<'info, MakerEscrow>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = maker_escrow,
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = authority,
+    )]
+    pub authority_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct OpenRfq<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+        has_one = user,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        associated_token::mint = temp_wallet.token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Rfq::SPACE,
+        seeds = [b"rfq", temp_wallet.key().as_ref()],
+        bump,
+    )]
+    pub rfq: Box<Account<'info, Rfq>>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: The pair's PoolPolicy PDA, checked in require_pool_access;
+    /// uninitialized for open pools
+    pub pool_policy: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitQuote<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"maker_escrow", authority.key().as_ref()],
+        bump = maker_escrow.bump,
+        has_one = authority,
+    )]
+    pub maker_escrow: Account<'info, MakerEscrow>,
+    #[account(
+        mut,
+        seeds = [b"rfq", rfq.temp_wallet.as_ref()],
+        bump = rfq.bump,
+    )]
+    pub rfq: Box<Account<'info, Rfq>>,
+}
+
+#[queue_computation_accounts("select_quote", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct SelectQuote<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"rfq", temp_wallet.key().as_ref()],
+        bump = rfq.bump,
+    )]
+    pub rfq: Box<Account<'info, Rfq>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SELECT_QUOTE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("select_quote")]
+#[derive(Accounts)]
+pub struct SelectQuoteCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_SELECT_QUOTE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub rfq: Box<Account<'info, Rfq>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleRfq<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"rfq", temp_wallet.key().as_ref()],
+        bump = rfq.bump,
+    )]
+    pub rfq: Box<Account<'info, Rfq>>,
+    #[account(
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
+    )]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(mut, address = rfq.makers[rfq.winner as usize] @ ErrorCode::InvalidMakerAccount)]
+    pub maker_escrow: Box<Account<'info, MakerEscrow>>,
+    #[account(address = temp_wallet.token_in_mint)]
+    pub token_in_mint: Box<Account<'info, Mint>>,
+    #[account(address = temp_wallet.token_out_mint)]
+    pub token_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = token_in_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_in_mint,
+        associated_token::authority = maker_escrow,
+    )]
+    pub maker_token_account_in: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = maker_escrow,
+    )]
+    pub maker_token_account_out: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseQuote<'info> {
+    #[account(
+        mut,
+        seeds = [b"rfq", rfq.temp_wallet.as_ref()],
+        bump = rfq.bump,
+    )]
+    pub rfq: Box<Account<'info, Rfq>>,
+    #[account(
+        mut,
+        seeds = [b"maker_escrow", maker_escrow.authority.as_ref()],
+        bump = maker_escrow.bump,
+    )]
+    pub maker_escrow: Account<'info, MakerEscrow>,
+}
+
+#[account]
+pub struct MakerEscrow {
+    pub authority: Pubkey,
+    // RFQs this maker has quoted into that haven't released it yet.
+    pub open_quotes: u32,
+    pub bump: u8,
+}
+
+impl MakerEscrow {
+    pub const SPACE: usize = 32 + 4 + 1;
+}
+
+#[account]
+pub struct Rfq {
+    pub temp_wallet: Pubkey,
+    pub deadline_slot: u64,
+    pub count: u8,
+    pub makers: [Pubkey; RFQ_QUOTES],
+    pub quote_keys: [[u8; 32]; RFQ_QUOTES],
+    pub quote_nonces: [u128; RFQ_QUOTES],
+    pub quotes: [[u8; 32]; RFQ_QUOTES],
+    pub pending: bool,
+    pub resolved: bool,
+    pub filled: bool,
+    pub winner: u8,
+    pub amount_out: u64,
+    // Quotes whose maker inventory release_quote has unlocked.
+    pub released: [bool; RFQ_QUOTES],
+    pub bump: u8,
+}
+
+impl Rfq {
+    pub const SPACE: usize = 32 + 8 + 1 + (32 + 32 + 16 + 32) * RFQ_QUOTES + 1 + 1 + 1 + 1 + 8 + RFQ_QUOTES + 1;
+}
+
+#[init_computation_definition_accounts("check_otc_terms", payer)]
+#[derive(Accounts)]
+pub struct InitCheckOtcTermsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct ProposeOtc<'info> {
+    #[account(mut)]
+    pub party_a: Signer<'info>,
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = party_a,
+        space = 8 + OtcDeal::SPACE,
+        seeds = [b"otc", party_a.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundOtcEscrow<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"otc", otc_deal.party_a.as_ref(), otc_deal.nonce.to_le_bytes().as_ref()],
+        bump = otc_deal.bump,
+        constraint = owner.key() == otc_deal.party_a || owner.key() == otc_deal.party_b @ ErrorCode::InvalidCounterparty,
+    )]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+    #[account(
+        constraint = mint.key() == otc_deal.mint_given_by(&owner.key()) @ ErrorCode::MintMismatch,
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + OtcEscrow::SPACE,
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub otc_escrow: Box<Account<'info, OtcEscrow>>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = mint,
+        associated_token::authority = otc_escrow,
+    )]
+    pub escrow_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = owner,
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("check_otc_terms", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CheckOtcTerms<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"otc", otc_deal.party_a.as_ref(), otc_deal.nonce.to_le_bytes().as_ref()],
+        bump = otc_deal.bump,
+    )]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+    #[account(
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), otc_deal.party_a.as_ref()],
+        bump = escrow_a.bump,
+    )]
+    pub escrow_a: Box<Account<'info, OtcEscrow>>,
+    #[account(
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), otc_deal.party_b.as_ref()],
+        bump = escrow_b.bump,
+    )]
+    pub escrow_b: Box<Account<'info, OtcEscrow>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_OTC_TERMS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("check_otc_terms")]
+#[derive(Accounts)]
+pub struct CheckOtcTermsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_OTC_TERMS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+}
+
+#[derive(Accounts)]
+pub struct SettleOtc<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"otc", otc_deal.party_a.as_ref(), otc_deal.nonce.to_le_bytes().as_ref()],
+        bump = otc_deal.bump,
+    )]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+    #[account(
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), otc_deal.party_a.as_ref()],
+        bump = escrow_a.bump,
+    )]
+    pub escrow_a: Box<Account<'info, OtcEscrow>>,
+    #[account(
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), otc_deal.party_b.as_ref()],
+        bump = escrow_b.bump,
+    )]
+    pub escrow_b: Box<Account<'info, OtcEscrow>>,
+    /// CHECK: Verified against the deal
+    #[account(address = otc_deal.party_a)]
+    pub party_a: UncheckedAccount<'info>,
+    /// CHECK: Verified against the deal
+    #[account(address = otc_deal.party_b)]
+    pub party_b: UncheckedAccount<'info>,
+    #[account(address = otc_deal.mint_a)]
+    pub mint_a: Box<Account<'info, Mint>>,
+    #[account(address = otc_deal.mint_b)]
+    pub mint_b: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow_a,
+    )]
+    pub escrow_a_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = escrow_b,
+    )]
+    pub escrow_b_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_b,
+        associated_token::authority = party_a,
+    )]
+    pub party_a_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_a,
+        associated_token::authority = party_b,
+    )]
+    pub party_b_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOtcEscrow<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"otc", otc_deal.party_a.as_ref(), otc_deal.nonce.to_le_bytes().as_ref()],
+        bump = otc_deal.bump,
+    )]
+    pub otc_deal: Box<Account<'info, OtcDeal>>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"otc_escrow", otc_deal.key().as_ref(), owner.key().as_ref()],
+        bump = otc_escrow.bump,
+        has_one = owner,
+    )]
+    pub otc_escrow: Box<Account<'info, OtcEscrow>>,
+    #[account(
+        mut,
+        associated_token::mint = otc_escrow.mint,
+        associated_token::authority = otc_escrow,
+    )]
+    pub escrow_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = otc_escrow.mint,
+        associated_token::authority = owner,
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct OtcDeal {
+    pub party_a: Pubkey,
+    pub party_b: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub nonce: u64,
+    pub expires_at_slot: u64,
+    pub funded_a: bool,
+    pub funded_b: bool,
+    pub pending: bool,
+    pub resolved: bool,
+    pub compatible: bool,
+    pub settled: bool,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub bump: u8,
+}
+
+impl OtcDeal {
+    pub const SPACE: usize = 32 * 4 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1;
+
+    pub fn mint_given_by(&self, party: &Pubkey) -> Pubkey {
+        if *party == self.party_a {
+            self.mint_a
+        } else {
+            self.mint_b
+        }
+    }
+}
+
+// One counterparty's side of an OTC deal; like a TempWallet, it owns the
+// deposited tokens and carries the order's ciphertexts.
+#[account]
+pub struct OtcEscrow {
+    pub deal: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub encrypted_give: [u8; 32],
+    pub encrypted_want_min: [u8; 32],
+    pub encryption_pubkey: [u8; 32],
+    pub encryption_nonce: u128,
+    pub bump: u8,
+}
+
+impl OtcEscrow {
+    pub const SPACE: usize = 32 + 32 + 32 + 8 + 32 + 32 + 32 + 16 + 1;
+}
+
+#[init_computation_definition_accounts("open_balance", payer)]
+#[derive(Accounts)]
+pub struct InitOpenBalanceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("credit_balance", payer)]
+#[derive(Accounts)]
+pub struct InitCreditBalanceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("debit_balance", payer)]
+#[derive(Accounts)]
+pub struct InitDebitBalanceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("cross_shielded", payer)]
+#[derive(Accounts)]
+pub struct InitCrossShieldedCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("open_balance", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct OpenShieldedBalance<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub mint: Box<Account<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ShieldedVault::SPACE,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: Box<Account<'info, ShieldedVault>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ShieldedBalance::SPACE,
+        seeds = [b"balance", payer.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_BALANCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("open_balance")]
+#[derive(Accounts)]
+pub struct OpenBalanceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_OPEN_BALANCE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[queue_computation_accounts("credit_balance", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct DepositShielded<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"balance", payer.key().as_ref(), shielded_balance.mint.as_ref()],
+        bump = shielded_balance.bump,
+    )]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        seeds = [b"vault", shielded_balance.mint.as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Box<Account<'info, ShieldedVault>>,
+    #[account(
+        mut,
+        associated_token::mint = shielded_balance.mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = shielded_balance.mint,
+        associated_token::authority = payer,
+    )]
+    pub payer_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CREDIT_BALANCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("credit_balance")]
+#[derive(Accounts)]
+pub struct CreditBalanceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CREDIT_BALANCE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[queue_computation_accounts("debit_balance", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct WithdrawShielded<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"balance", payer.key().as_ref(), shielded_balance.mint.as_ref()],
+        bump = shielded_balance.bump,
+    )]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DEBIT_BALANCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("debit_balance")]
+#[derive(Accounts)]
+pub struct DebitBalanceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_DEBIT_BALANCE)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseStuckBalance<'info> {
+    #[account(
+        mut,
+        seeds = [b"balance", shielded_balance.owner.as_ref(), shielded_balance.mint.as_ref()],
+        bump = shielded_balance.bump,
+    )]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimShieldedWithdrawal<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"balance", owner.key().as_ref(), shielded_balance.mint.as_ref()],
+        bump = shielded_balance.bump,
+        has_one = owner,
+    )]
+    pub shielded_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(address = shielded_balance.mint)]
+    pub mint: Box<Account<'info, Mint>>,
+    #[account(
+        seeds = [b"vault", mint.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Box<Account<'info, ShieldedVault>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = mint,
+        associated_token::authority = owner,
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct PlaceShieldedOrder<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_in_mint: Box<Account<'info, Mint>>,
+    pub token_out_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + ShieldedOrder::SPACE,
+        seeds = [b"shielded_order", owner.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub shielded_order: Box<Account<'info, ShieldedOrder>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseShieldedOrder<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"shielded_order", owner.key().as_ref(), shielded_order.nonce.to_le_bytes().as_ref()],
+        bump = shielded_order.bump,
+        has_one = owner,
+    )]
+    pub shielded_order: Box<Account<'info, ShieldedOrder>>,
+}
+
+#[queue_computation_accounts("cross_shielded", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CrossShieldedOrders<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"shielded_order", order_a.owner.as_ref(), order_a.nonce.to_le_bytes().as_ref()],
+        bump = order_a.bump,
+    )]
+    pub order_a: Box<Account<'info, ShieldedOrder>>,
+    #[account(
+        mut,
+        seeds = [b"shielded_order", order_b.owner.as_ref(), order_b.nonce.to_le_bytes().as_ref()],
+        bump = order_b.bump,
+    )]
+    pub order_b: Box<Account<'info, ShieldedOrder>>,
+    #[account(
+        mut,
+        seeds = [b"balance", order_a.owner.as_ref(), order_a.token_in_mint.as_ref()],
+        bump = a_in_balance.bump,
+    )]
+    pub a_in_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        mut,
+        seeds = [b"balance", order_a.owner.as_ref(), order_a.token_out_mint.as_ref()],
+        bump = a_out_balance.bump,
+    )]
+    pub a_out_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        mut,
+        seeds = [b"balance", order_b.owner.as_ref(), order_b.token_in_mint.as_ref()],
+        bump = b_in_balance.bump,
+    )]
+    pub b_in_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        mut,
+        seeds = [b"balance", order_b.owner.as_ref(), order_b.token_out_mint.as_ref()],
+        bump = b_out_balance.bump,
+    )]
+    pub b_out_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CROSS_SHIELDED))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("cross_shielded")]
+#[derive(Accounts)]
+pub struct CrossShieldedCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CROSS_SHIELDED)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub order_a: Box<Account<'info, ShieldedOrder>>,
+    #[account(mut)]
+    pub order_b: Box<Account<'info, ShieldedOrder>>,
+    #[account(mut)]
+    pub a_in_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(mut)]
+    pub a_out_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(mut)]
+    pub b_in_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(mut)]
+    pub b_out_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[init_computation_definition_accounts("private_transfer", payer)]
+#[derive(Accounts)]
+pub struct InitPrivateTransferCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("private_transfer", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct PrivateTransfer<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"balance", payer.key().as_ref(), from_balance.mint.as_ref()],
+        bump = from_balance.bump,
+    )]
+    pub from_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        mut,
+        seeds = [b"balance", to_balance.owner.as_ref(), from_balance.mint.as_ref()],
+        bump = to_balance.bump,
+    )]
+    pub to_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_PRIVATE_TRANSFER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("private_transfer")]
+#[derive(Accounts)]
+pub struct PrivateTransferCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_PRIVATE_TRANSFER)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub from_balance: Box<Account<'info, ShieldedBalance>>,
+    #[account(mut)]
+    pub to_balance: Box<Account<'info, ShieldedBalance>>,
+}
+
+#[account]
+pub struct ShieldedVault {
+    pub mint: Pubkey,
+    pub bump: u8,
+}
+
+impl ShieldedVault {
+    pub const SPACE: usize = 32 + 1;
+}
+
+// The encrypted balance leads so circuits can read it at offset 8.
+#[account]
+pub struct ShieldedBalance {
+    pub encrypted_balance: [[u8; 32]; BALANCE_CIPHERTEXTS],
+    pub balance_nonce: u128,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub pending: bool,
+    pub pending_since: u64,
+    pub pending_deposit: u64,
+    pub requested_withdrawal: u64,
+    pub withdrawable: u64,
+    pub bump: u8,
+}
+
+impl ShieldedBalance {
+    pub const SPACE: usize = 32 * BALANCE_CIPHERTEXTS + 16 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct ShieldedOrder {
+    pub owner: Pubkey,
+    pub token_in_mint: Pubkey,
+    pub token_out_mint: Pubkey,
+    pub nonce: u64,
+    pub encrypted_amount_in: [u8; 32],
+    pub encrypted_amount_out_min: [u8; 32],
+    pub encryption_pubkey: [u8; 32],
+    pub encryption_nonce: u128,
+    pub active: bool,
+    pub pending: bool,
+    pub pending_since: u64,
+    pub bump: u8,
+}
+
+impl ShieldedOrder {
+    pub const SPACE: usize = 32 + 32 + 32 + 8 + 32 + 32 + 32 + 16 + 1 + 1 + 8 + 1;
+}
+
+#[init_computation_definition_accounts("compute_fee", payer)]
+#[derive(Accounts)]
+pub struct InitComputeFeeCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = derive_mxe_pda!()
+    )]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitFeeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeConfig::SPACE,
+        seeds = [b"fee_config"],
+        bump,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, program::Dex>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::NotUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeSchedule<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"fee_config"],
+        bump = fee_config.bump,
+        has_one = authority,
+    )]
+    pub fee_config: Box<Account<'info, FeeConfig>>,
+    pub mint: Box<Account<'info, Mint>>,
+    /// CHECK: Fee recipient named in the config
+    #[account(address = fee_config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + FeeSchedule::SPACE,
+        seeds = [b"fee_schedule", mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_schedule: Box<Account<'info, FeeSchedule>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("compute_fee", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct AssessFee<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
         mut,
-        address = derive_cluster_pda!(mxe_account)
+        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
+        bump = temp_wallet.bump,
     )]
-    pub cluster_account: Account<'info, Cluster>,
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
     #[account(
-        mut,
-        address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS,
+        associated_token::mint = temp_wallet.token_out_mint,
+        associated_token::authority = temp_wallet,
+    )]
+    pub temp_token_account_out: Box<Account<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"fee_schedule", temp_wallet.token_out_mint.as_ref()],
+        bump = fee_schedule.bump,
+    )]
+    pub fee_schedule: Box<Account<'info, FeeSchedule>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + TraderVolume::SPACE,
+        seeds = [b"volume", temp_wallet.user.as_ref(), temp_wallet.token_out_mint.as_ref()],
+        bump,
     )]
-    pub pool_account: Account<'info, FeePool>,
+    pub trader_volume: Box<Account<'info, TraderVolume>>,
     #[account(
-        address = ARCIUM_CLOCK_ACCOUNT_ADDRESS
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
     )]
-    pub clock_account: Account<'info, ClockAccount>,
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_COMPUTE_FEE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
     pub system_program: Program<'info, System>,
     pub arcium_program: Program<'info, Arcium>,
 }
 
-#[callback_accounts("match_order")]
+#[callback_accounts("compute_fee")]
 #[derive(Accounts)]
-pub struct MatchOrderCallback<'info> {
+pub struct ComputeFeeCallback<'info> {
     pub arcium_program: Program<'info, Arcium>,
     #[account(
-        address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER)
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_COMPUTE_FEE)
     )]
     pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
     #[account(mut)]
-    /// CHECK: Validated manually in instruction
-    pub settlement_request: UncheckedAccount<'info>,
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    #[account(mut)]
+    pub trader_volume: Box<Account<'info, TraderVolume>>,
+}
+
+#[account]
+pub struct FeeConfig {
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub bump: u8,
+}
+
+impl FeeConfig {
+    pub const SPACE: usize = 32 + 32 + 1;
+}
+
+#[account]
+pub struct FeeSchedule {
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+    pub thresholds: [u64; FEE_TIERS - 1],
+    pub bps: [u16; FEE_TIERS],
+    pub bump: u8,
+}
+
+impl FeeSchedule {
+    pub const SPACE: usize = 32 + 32 + 8 * (FEE_TIERS - 1) + 2 * FEE_TIERS + 1;
+}
+
+// The encrypted volume leads so compute_fee can read it at offset 8.
+#[account]
+pub struct TraderVolume {
+    pub encrypted_volume: [[u8; 32]; VOLUME_CIPHERTEXTS],
+    pub volume_nonce: u128,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub epoch: u64,
+    pub initialized: bool,
+    pub pending: bool,
+    pub bump: u8,
+}
+
+impl TraderVolume {
+    pub const SPACE: usize = 32 * VOLUME_CIPHERTEXTS + 16 + 32 + 32 + 8 + 1 + 1 + 1;
 }
 
 #[derive(Accounts)]
-pub struct ExecuteSwap<'info> {
+pub struct InitAuditConfig<'info> {
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
     #[account(
-        mut,
-        close = payer, // Close after execution to refund rent
+        init,
+        payer = authority,
+        space = 8 + AuditConfig::SPACE,
+        seeds = [b"audit_config"],
+        bump,
     )]
-    pub settlement_request: Account<'info, SettlementRequest>,
+    pub audit_config: Account<'info, AuditConfig>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, program::Dex>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::NotUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAuditor<'info> {
+    pub authority: Signer<'info>,
     #[account(
-        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
-        bump = temp_wallet.bump
+        mut,
+        seeds = [b"audit_config"],
+        bump = audit_config.bump,
+        has_one = authority,
     )]
-    pub temp_wallet: Account<'info, TempWallet>,
-    
+    pub audit_config: Account<'info, AuditConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitPoolPolicy<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
     #[account(
-        seeds = [b"token", settlement_request.token_in.to_le_bytes().as_ref()],
+        init,
+        payer = payer,
+        space = 8 + PoolPolicy::SPACE,
+        seeds = [b"pool_policy", mint_a.key().as_ref(), mint_b.key().as_ref()],
         bump,
     )]
-    pub token_in_mapping: Account<'info, TokenMapping>,
-    
+    pub pool_policy: Account<'info, PoolPolicy>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, program::Dex>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ ErrorCode::NotUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPoolPolicy<'info> {
+    pub authority: Signer<'info>,
     #[account(
-        seeds = [b"token", settlement_request.token_out.to_le_bytes().as_ref()],
-        bump,
+        mut,
+        seeds = [b"pool_policy", pool_policy.mint_a.as_ref(), pool_policy.mint_b.as_ref()],
+        bump = pool_policy.bump,
+        has_one = authority,
     )]
-    pub token_out_mapping: Account<'info, TokenMapping>,
-
-    /// CHECK: Jupiter Program ID
-    pub jupiter_program: UncheckedAccount<'info>,
+    pub pool_policy: Account<'info, PoolPolicy>,
 }
 
 #[account]
-pub struct TokenMapping {
-    pub mint: Pubkey,
-    pub id: u64,
+pub struct AuditConfig {
+    pub authority: Pubkey,
+    pub auditor_key: [u8; 32],
+    pub enabled: bool,
+    pub bump: u8,
+}
+
+impl AuditConfig {
+    pub const SPACE: usize = 32 + 32 + 1 + 1;
 }
 
+// The full order re-encrypted to the auditor by match_order. `auditor_key` is
+// zero when the order was queued without auditing, and `revision` says which
+// amendment the ciphertexts describe.
 #[account]
-pub struct SettlementRequest {
-    pub amount_in: u64,
-    pub min_out: u64,
-    pub token_in: u64,
-    pub token_out: u64,
+pub struct OrderDisclosure {
     pub nonce: u64,
-    pub active: bool,
+    pub auditor_key: [u8; 32],
+    pub encryption_nonce: u128,
+    pub ciphertexts: [[u8; 32]; SWAP_ORDER_CIPHERTEXTS],
+    pub revision: u8,
     pub bump: u8,
 }
 
+impl OrderDisclosure {
+    pub const SPACE: usize = 8 + 32 + 16 + 32 * SWAP_ORDER_CIPHERTEXTS + 1 + 1;
+}
 
-#[init_computation_definition_accounts("match_order", payer)]
+#[init_computation_definition_accounts("store_policy", payer)]
 #[derive(Accounts)]
-pub struct InitMatchOrderCompDef<'info> {
+pub struct InitStorePolicyCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
@@ -556,60 +8742,46 @@ pub struct InitMatchOrderCompDef<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[init_computation_definition_accounts("check_access", payer)]
 #[derive(Accounts)]
-#[instruction(amount_in: u64, min_out: u64, nonce: u64)]
-pub struct CreatePrivateSwap<'info> {
+pub struct InitCheckAccessCompDef<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-        init,
-        payer = payer,
-        space = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 1 + 1 + 8 + 32 + 32 + 32 + 32 + 32 + 1,
-        seeds = [b"temp_wallet", payer.key().as_ref(), nonce.to_le_bytes().as_ref()],
-        bump
+        mut,
+        address = derive_mxe_pda!()
     )]
-    pub temp_wallet: Account<'info, TempWallet>,
-    pub token_in_mint: Account<'info, Mint>,
-    pub token_out_mint: Account<'info, Mint>,
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
 
-#[queue_computation_accounts("match_order", payer)]
+#[queue_computation_accounts("store_policy", authority)]
 #[derive(Accounts)]
 #[instruction(computation_offset: u64)]
-pub struct FundAndPlaceOrder<'info> {
+pub struct SetAccessPolicy<'info> {
     #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"temp_wallet", temp_wallet.user.as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
-        bump = temp_wallet.bump
-    )]
-    pub temp_wallet: Box<Account<'info, TempWallet>>,
+    pub authority: Signer<'info>,
     #[account(
-        associated_token::mint = temp_wallet.token_in_mint,
-        associated_token::authority = temp_wallet,
+        init_if_needed,
+        payer = authority,
+        space = 8 + AccessPolicy::SPACE,
+        seeds = [b"access_policy", authority.key().as_ref()],
+        bump,
     )]
-    pub temp_token_account: Box<Account<'info, TokenAccount>>,
-    
-    // Accounts for place_order logic
+    pub access_policy: Box<Account<'info, AccessPolicy>>,
     #[account(
         init_if_needed,
         space = 9,
-        payer = payer,
+        payer = authority,
         seeds = [&SIGN_PDA_SEED],
         bump,
         address = derive_sign_pda!(),
     )]
     pub sign_pda_account: Box<Account<'info, SignerAccount>>,
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1,
-        seeds = [b"settlement", temp_wallet.nonce.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub settlement_request: Box<Account<'info, SettlementRequest>>,
     #[account(address = derive_mxe_pda!())]
     pub mxe_account: Box<Account<'info, MXEAccount>>,
     #[account(mut, address = derive_mempool_pda!())]
@@ -621,7 +8793,7 @@ pub struct FundAndPlaceOrder<'info> {
     #[account(mut, address = derive_comp_pda!(computation_offset))]
     /// CHECK: computation_account
     pub computation_account: UncheckedAccount<'info>,
-    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDER))]
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_POLICY))]
     pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
     #[account(mut, address = derive_cluster_pda!(mxe_account))]
     pub cluster_account: Box<Account<'info, Cluster>>,
@@ -629,42 +8801,195 @@ pub struct FundAndPlaceOrder<'info> {
     pub pool_account: Box<Account<'info, FeePool>>,
     #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
     pub clock_account: Box<Account<'info, ClockAccount>>,
-    
+
     pub system_program: Program<'info, System>,
     pub arcium_program: Program<'info, Arcium>,
 }
 
+#[callback_accounts("store_policy")]
 #[derive(Accounts)]
-pub struct ReturnTokensToUser<'info> {
+pub struct StorePolicyCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_POLICY)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub access_policy: Box<Account<'info, AccessPolicy>>,
+}
+
+#[derive(Accounts)]
+pub struct IssueAttestation<'info> {
+    #[account(mut)]
+    pub attester: Signer<'info>,
+    #[account(
+        seeds = [b"access_policy", access_policy.authority.as_ref()],
+        bump = access_policy.bump,
+        has_one = attester,
+    )]
+    pub access_policy: Box<Account<'info, AccessPolicy>>,
+    /// CHECK: Wallet the attestation is issued to
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = attester,
+        space = 8 + Attestation::SPACE,
+        seeds = [b"attestation", access_policy.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub attestation: Box<Account<'info, Attestation>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("check_access", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RequestAccess<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
     #[account(
         mut,
-        close = user,
         seeds = [b"temp_wallet", user.key().as_ref(), temp_wallet.nonce.to_le_bytes().as_ref()],
         bump = temp_wallet.bump,
         has_one = user,
     )]
-    pub temp_wallet: Account<'info, TempWallet>,
-    /// CHECK: Verified by has_one on temp_wallet
-    #[account(mut)]
-    pub user: SystemAccount<'info>,
-    
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
     #[account(
-        mut,
-        associated_token::mint = temp_wallet.token_out_mint,
-        associated_token::authority = temp_wallet,
+        seeds = [b"access_policy", access_policy.authority.as_ref()],
+        bump = access_policy.bump,
     )]
-    pub temp_token_account_out: Account<'info, TokenAccount>,
-    
+    pub access_policy: Box<Account<'info, AccessPolicy>>,
     #[account(
-        mut,
-        associated_token::mint = temp_wallet.token_out_mint,
-        associated_token::authority = user,
+        seeds = [b"attestation", access_policy.key().as_ref(), user.key().as_ref()],
+        bump = attestation.bump,
     )]
-    pub user_token_account_out: Account<'info, TokenAccount>,
-    
-    pub token_program: Program<'info, Token>,
+    pub attestation: Box<Account<'info, Attestation>>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Box<Account<'info, SignerAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_ACCESS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("check_access")]
+#[derive(Accounts)]
+pub struct CheckAccessCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_ACCESS)
+    )]
+    pub comp_def_account: Account<'info, ComputationDefinitionAccount>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub temp_wallet: Box<Account<'info, TempWallet>>,
+}
+
+// The encrypted policy leads so check_access can read it at offset 8.
+#[account]
+pub struct AccessPolicy {
+    pub encrypted_policy: [[u8; 32]; ACCESS_POLICY_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub authority: Pubkey,
+    pub attester: Pubkey,
+    pub initialized: bool,
+    pub pending: bool,
+    pub bump: u8,
+}
+
+impl AccessPolicy {
+    pub const SPACE: usize = 32 * ACCESS_POLICY_CIPHERTEXTS + 16 + 32 + 32 + 1 + 1 + 1;
+}
+
+// Access policy a pair is bound to. Mints are stored in ascending order.
+// A change staged by set_pool_policy replaces `access_policy` once the
+// clock reaches `effective_slot`.
+#[account]
+pub struct PoolPolicy {
+    pub authority: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub access_policy: Pubkey,
+    pub next_access_policy: Pubkey,
+    pub effective_slot: u64,
+    pub bump: u8,
+}
+
+impl PoolPolicy {
+    pub const SPACE: usize = 32 + 32 + 32 + 32 + 32 + 8 + 1;
+
+    /// The policy gating the pair at `slot`.
+    pub fn active_policy(&self, slot: u64) -> Pubkey {
+        if slot >= self.effective_slot {
+            self.next_access_policy
+        } else {
+            self.access_policy
+        }
+    }
+}
+
+// Jurisdiction and tier, encrypted by the policy's attester.
+#[account]
+pub struct Attestation {
+    pub policy: Pubkey,
+    pub user: Pubkey,
+    pub encryption_key: [u8; 32],
+    pub encryption_nonce: u128,
+    pub ciphertexts: [[u8; 32]; ATTESTATION_CIPHERTEXTS],
+    pub bump: u8,
+}
+
+impl Attestation {
+    pub const SPACE: usize = 32 + 32 + 32 + 16 + 32 * ATTESTATION_CIPHERTEXTS + 1;
+}
+
+// The encrypted schedule leads so next_slice can read it at offset 8.
+#[account]
+pub struct TwapSchedule {
+    pub encrypted_state: [[u8; 32]; TWAP_STATE_CIPHERTEXTS],
+    pub state_nonce: u128,
+    pub nonce: u64,
+    pub temp_wallet: Pubkey,
+    pub interval_slots: u64,
+    pub next_slot: u64,
+    pub slices_released: u32,
+    pub pending: bool,
+    pub finished: bool,
+    pub bump: u8,
+}
+
+impl TwapSchedule {
+    pub const SPACE: usize = 32 * TWAP_STATE_CIPHERTEXTS + 16 + 8 + 32 + 8 + 8 + 4 + 1 + 1 + 1;
 }
 
 #[account]
@@ -673,7 +8998,6 @@ pub struct TempWallet {
     pub token_in_mint: Pubkey,
     pub token_out_mint: Pubkey,
     pub amount_in: u64,
-    pub min_out: u64,
     pub nonce: u64,
     pub active: bool,
     pub is_funded: bool,
@@ -681,12 +9005,128 @@ pub struct TempWallet {
     pub computation_offset: u64,
     pub encrypted_amount_in: [u8; 32],
     pub encrypted_amount_out_min: [u8; 32],
-    pub encrypted_token_in: [u8; 32],
-    pub encrypted_token_out: [u8; 32],
+    pub encrypted_token_in: [[u8; 32]; 2],
+    pub encrypted_token_out: [[u8; 32]; 2],
     pub encrypted_nonce: [u8; 32],
+    pub encrypted_limit_price: [u8; 32],
+    pub encrypted_stop_price: [u8; 32],
+    pub encrypted_take_profit_price: [u8; 32],
+    pub encrypted_display_size: [u8; 32],
+    pub encrypted_expires_at_slot: [u8; 32],
+    // x25519 key and nonce the order was encrypted under
+    pub encryption_pubkey: [u8; 32],
+    pub encryption_nonce: u128,
+    pub order_type: OrderType,
+    // Pyth feed a triggered order is armed against; default for market orders.
+    pub price_feed: Pubkey,
+    pub trigger_above: bool,
+    // Bumped by amend_order and cancel_order; match results carrying an
+    // older revision are ignored.
+    pub revision: u8,
+    // Output already run through assess_fee and the fee it came to.
+    pub fee_base: u64,
+    pub fee_due: u64,
+    pub fee_pending: bool,
+    // Policy this order asked to be admitted under, and whether check_access
+    // let it in. Default for orders outside any permissioned pool.
+    pub access_policy: Pubkey,
+    pub access_granted: bool,
+    pub access_pending: bool,
+    // Where the order stands in fund_and_cross_orders or
+    // fund_and_cross_at_mid, and when a queued cross stops holding the
+    // deposit.
+    pub cross_status: CrossStatus,
+    pub cross_deadline_slot: u64,
+    pub bump: u8,
+}
+
+impl TempWallet {
+    pub fn set_order(&mut self, order: &EncryptedOrder) {
+        self.encrypted_amount_in = order.amount_in;
+        self.encrypted_amount_out_min = order.amount_out_min;
+        self.encrypted_token_in = order.token_in;
+        self.encrypted_token_out = order.token_out;
+        self.encrypted_nonce = order.nonce;
+        self.encrypted_limit_price = order.limit_price;
+        self.encrypted_stop_price = order.stop_price;
+        self.encrypted_take_profit_price = order.take_profit_price;
+        self.encrypted_display_size = order.display_size;
+        self.encrypted_expires_at_slot = order.expires_at_slot;
+        self.encryption_pubkey = order.encryption_pubkey;
+        self.encryption_nonce = order.encryption_nonce;
+    }
+}
+
+// A SwapOrder's ciphertexts, field for field, with the x25519 key and nonce
+// they were encrypted under.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedOrder {
+    pub amount_in: [u8; 32],
+    pub amount_out_min: [u8; 32],
+    pub token_in: [[u8; 32]; 2],
+    pub token_out: [[u8; 32]; 2],
+    pub nonce: [u8; 32],
+    pub limit_price: [u8; 32],
+    pub stop_price: [u8; 32],
+    pub take_profit_price: [u8; 32],
+    pub display_size: [u8; 32],
+    pub expires_at_slot: [u8; 32],
+    pub encryption_pubkey: [u8; 32],
+    pub encryption_nonce: u128,
+}
+
+// Discriminants must match the ORDER_* constants in encrypted-ixs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderType {
+    Market = 0,
+    Limit = 1,
+    StopLoss = 2,
+    TakeProfit = 3,
+    Twap = 4,
+    Iceberg = 5,
+    /// Only crosses at the mid of `price_feed`; `trigger_above` marks the
+    /// side selling the feed's base asset.
+    Midpoint = 6,
+}
+
+impl OrderType {
+    pub fn uses_price_feed(self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::StopLoss | OrderType::TakeProfit)
+    }
+
+    /// Orders created against a Pyth feed: the triggered kinds plus Midpoint.
+    pub fn names_price_feed(self) -> bool {
+        self.uses_price_feed() || self == OrderType::Midpoint
+    }
+
+    /// Orders that stay inactive after matching until a trigger fires or a
+    /// TWAP slice is released.
+    pub fn waits_for_release(self) -> bool {
+        self.uses_price_feed() || self == OrderType::Twap
+    }
+
+    /// Orders executed over several rounds, each checked by its own circuit
+    /// instead of verify_fill.
+    pub fn worked_in_rounds(self) -> bool {
+        matches!(self, OrderType::Twap | OrderType::Iceberg)
+    }
+}
+
+// Match result re-encrypted to the submitter's key.
+#[account]
+pub struct OrderReceipt {
+    pub user: Pubkey,
+    pub nonce: u64,
+    pub encryption_key: [u8; 32],
+    pub encryption_nonce: u128,
+    pub ciphertexts: [[u8; 32]; FILL_RECEIPT_CIPHERTEXTS],
     pub bump: u8,
 }
 
+impl OrderReceipt {
+    pub const SPACE: usize = 32 + 8 + 32 + 16 + 32 * FILL_RECEIPT_CIPHERTEXTS + 1;
+}
+
 #[event]
 pub struct TempWalletCreated {
     pub temp_wallet: Pubkey,
@@ -700,10 +9140,222 @@ pub struct TempWalletCreated {
 #[event]
 pub struct OrderSettledEvent {
     pub amount_in: u64,
-    pub min_out: u64,
-    pub token_in: u64,
-    pub token_out: u64,
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct OrderRejectedEvent {
+    pub nonce: u64,
+}
+
+// `late` is set when the MPC result missed its deadline rather than the
+// order's own expiry passing.
+#[event]
+pub struct OrderExpiredEvent {
+    pub nonce: u64,
+    pub late: bool,
+}
+
+#[event]
+pub struct OrderCancelledEvent {
+    pub nonce: u64,
+}
+
+#[event]
+pub struct OrderAmendedEvent {
+    pub nonce: u64,
+    pub revision: u8,
+}
+
+#[event]
+pub struct FillCheckedEvent {
+    pub nonce: u64,
+    pub passed: bool,
+}
+
+#[event]
+pub struct FillUnwoundEvent {
+    pub nonce: u64,
+    pub unwound_out: u64,
+}
+
+#[event]
+pub struct OrdersCrossedEvent {
+    pub nonce_a: u64,
+    pub nonce_b: u64,
+    pub crossed: bool,
+    pub fill_a: u64,
+    pub fill_b: u64,
+}
+
+#[event]
+pub struct BatchJoinedEvent {
+    pub batch: Pubkey,
+    pub count: u8,
+}
+
+#[event]
+pub struct BatchClearedEvent {
+    pub batch: Pubkey,
+    pub clearing_price: u128,
+    pub count: u8,
+}
+
+#[event]
+pub struct BatchAbortedEvent {
+    pub batch: Pubkey,
+    pub count: u8,
+}
+
+#[event]
+pub struct SequenceJoinedEvent {
+    pub sequence: Pubkey,
+    pub count: u8,
+}
+
+#[event]
+pub struct SequenceShuffledEvent {
+    pub sequence: Pubkey,
+    pub execution_order: [u8; BATCH_SIZE],
+    pub count: u8,
+}
+
+#[event]
+pub struct SequenceReleasedEvent {
+    pub sequence: Pubkey,
+    pub settlement: Pubkey,
+    pub position: u8,
+}
+
+// Bands are orders of magnitude; see reveal_pair_stats in encrypted-ixs.
+#[event]
+pub struct PairStatsRevealedEvent {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub epoch: u64,
+    pub base_volume_band: u8,
+    pub quote_volume_band: u8,
+    pub order_count_band: u8,
+}
+
+#[event]
+pub struct OrderRestedEvent {
+    pub order_book: Pubkey,
+    pub temp_wallet: Pubkey,
+    pub slot: u8,
+    pub accepted: bool,
+}
+
+#[event]
+pub struct BookMatchedEvent {
+    pub order_book: Pubkey,
+    pub temp_wallet: Pubkey,
+    pub matched: bool,
+    pub slot: u8,
+    pub taker_gives: u64,
+    pub taker_receives: u64,
+}
+
+#[event]
+pub struct PartialFillEvent {
+    pub nonce: u64,
+    pub round: u32,
+    pub passed: bool,
+    pub done: bool,
+}
+
+#[event]
+pub struct OrderArmedEvent {
+    pub nonce: u64,
+    pub order_type: OrderType,
+    pub price_feed: Pubkey,
+}
+
+#[event]
+pub struct TriggerCheckedEvent {
+    pub nonce: u64,
+    pub fired: bool,
+}
+
+#[event]
+pub struct TwapSliceEvent {
+    pub nonce: u64,
+    pub slice: u32,
+    pub amount: u64,
+    pub passed: bool,
+    pub done: bool,
+}
+
+#[event]
+pub struct IcebergRefilledEvent {
+    pub nonce: u64,
+    pub clip: u64,
+    pub passed: bool,
+    pub done: bool,
+}
+
+#[event]
+pub struct QuoteSubmittedEvent {
+    pub rfq: Pubkey,
+    pub maker: Pubkey,
+    pub count: u8,
+}
+
+#[event]
+pub struct RfqResolvedEvent {
+    pub rfq: Pubkey,
+    pub filled: bool,
+    pub maker: Pubkey,
+    pub amount_out: u64,
+}
+
+#[event]
+pub struct OtcCheckedEvent {
+    pub deal: Pubkey,
+    pub compatible: bool,
+}
+
+#[event]
+pub struct OtcSettledEvent {
+    pub deal: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[event]
+pub struct ShieldedWithdrawalEvent {
+    pub balance: Pubkey,
+    pub amount: u64,
+    pub ok: bool,
+}
+
+#[event]
+pub struct ShieldedCrossEvent {
+    pub order_a: Pubkey,
+    pub order_b: Pubkey,
+    pub crossed: bool,
+}
+
+#[event]
+pub struct PrivateTransferEvent {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub ok: bool,
+}
+
+#[event]
+pub struct FeeAssessedEvent {
     pub nonce: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct AccessCheckedEvent {
+    pub temp_wallet: Pubkey,
+    pub access_policy: Pubkey,
+    pub allowed: bool,
 }
 
 #[error_code]
@@ -722,13 +9374,425 @@ pub enum ErrorCode {
     AlreadyFunded,
     #[msg("Insufficient funds in temp wallet")]
     InsufficientFunds,
+    #[msg("Encrypted order does not match the Temp Wallet")]
+    OrderBindingMismatch,
+    #[msg("Invalid Receipt PDA")]
+    InvalidReceiptPDA,
+    #[msg("Settlement is not awaiting fill verification")]
+    FillNotAwaitingVerification,
+    #[msg("Orders are not on opposite sides of the same pair")]
+    MintMismatch,
+    #[msg("Batch epoch does not match the current slot")]
+    BatchEpochMismatch,
+    #[msg("Batch has a computation in flight")]
+    BatchBusy,
+    #[msg("Batch is no longer accepting orders")]
+    BatchClosed,
+    #[msg("Batch is full")]
+    BatchFull,
+    #[msg("Batch cannot be cleared yet")]
+    BatchNotReady,
+    #[msg("Batch has not been cleared")]
+    BatchNotCleared,
+    #[msg("Invalid batch slot")]
+    InvalidBatchSlot,
+    #[msg("Already settled")]
+    AlreadySettled,
+    #[msg("Order book has a computation in flight")]
+    BookBusy,
+    #[msg("Order book is full")]
+    BookFull,
+    #[msg("Invalid order book slot")]
+    InvalidBookSlot,
+    #[msg("Book match does not belong to this order book")]
+    InvalidBookMatch,
+    #[msg("Book match has not been resolved")]
+    BookMatchPending,
+    #[msg("Partial fill window has expired")]
+    PartialFillExpired,
+    #[msg("Order is being filled in rounds; use verify_partial_fill")]
+    PartialFillOrder,
+    #[msg("Order can no longer switch to partial fills")]
+    PartialFillUnavailable,
+    #[msg("Settlement is not waiting on a price trigger")]
+    NotAwaitingTrigger,
+    #[msg("Order's price trigger has not fired")]
+    TriggerNotPassed,
+    #[msg("Order is worked in rounds; its fills are checked by its own crank")]
+    RoundBasedOrder,
+    #[msg("Order is not a TWAP")]
+    NotTwapOrder,
+    #[msg("TWAP interval must be at least one slot")]
+    InvalidTwapInterval,
+    #[msg("Next TWAP slice is not due yet")]
+    TwapSliceNotDue,
+    #[msg("Swap spent more than the released TWAP slice")]
+    TwapSliceExceeded,
+    #[msg("TWAP schedule has finished")]
+    TwapFinished,
+    #[msg("TWAP schedule has a computation in flight")]
+    TwapBusy,
+    #[msg("Order is not an iceberg")]
+    NotIcebergOrder,
+    #[msg("Price account is not a valid Pyth price feed")]
+    InvalidPriceFeed,
+    #[msg("Oracle price is stale or not trading")]
+    StaleOraclePrice,
+    #[msg("RFQ only supports market orders")]
+    RfqMarketOnly,
+    #[msg("RFQ is no longer accepting quotes")]
+    RfqClosed,
+    #[msg("RFQ already holds the maximum number of quotes")]
+    RfqFull,
+    #[msg("Maker has already quoted this RFQ")]
+    DuplicateQuote,
+    #[msg("RFQ cannot be resolved yet")]
+    RfqNotReady,
+    #[msg("RFQ was not filled")]
+    RfqNotFilled,
+    #[msg("Account does not belong to a quoting maker")]
+    InvalidMakerAccount,
+    #[msg("Signer is not a party to this OTC deal")]
+    InvalidCounterparty,
+    #[msg("OTC deal has expired")]
+    OtcExpired,
+    #[msg("OTC deal is no longer open")]
+    OtcClosed,
+    #[msg("Both sides of the OTC deal must be funded")]
+    OtcNotFunded,
+    #[msg("OTC terms were not found compatible")]
+    OtcNotCompatible,
+    #[msg("OTC escrow cannot be closed before settlement or expiry")]
+    OtcNotExpired,
+    #[msg("Order is not a Midpoint order")]
+    NotMidpointOrder,
+    #[msg("Midpoint orders only cross through fund_and_cross_at_mid")]
+    MidpointOrder,
+    #[msg("Order A must sell the feed's base asset and order B buy it")]
+    MidpointSideMismatch,
+    #[msg("Shielded balance has a computation in flight")]
+    BalanceBusy,
+    #[msg("Claim the previous withdrawal first")]
+    WithdrawalUnclaimed,
+    #[msg("No approved withdrawal to claim")]
+    NothingToWithdraw,
+    #[msg("Order has started executing and can no longer be amended")]
+    OrderNotAmendable,
+    #[msg("Fee thresholds must ascend and tiers may not exceed the fee cap")]
+    InvalidFeeSchedule,
+    #[msg("Fee assessment has a computation in flight")]
+    FeeBusy,
+    #[msg("No new output to assess a fee on")]
+    NothingToAssess,
+    #[msg("Output must go through assess_fee before it is returned")]
+    FeeNotAssessed,
+    #[msg("Treasury account does not match the fee schedule")]
+    InvalidTreasury,
+    #[msg("Sequencing round is no longer taking orders")]
+    SequenceClosed,
+    #[msg("Sequencing round is full")]
+    SequenceFull,
+    #[msg("Only matched, unexecuted market orders can be sequenced")]
+    NotSequenceable,
+    #[msg("Sequencing round is not ready to shuffle")]
+    SequenceNotReady,
+    #[msg("Sequencing round has not been shuffled")]
+    SequenceNotShuffled,
+    #[msg("Every order in the round has been released")]
+    SequenceDone,
+    #[msg("Previous order in the sequence still holds its turn")]
+    SequenceTurnPending,
+    #[msg("Settlement does not match the round's execution order")]
+    InvalidSequenceAccount,
+    #[msg("Stats epoch does not match the current slot")]
+    StatsEpochMismatch,
+    #[msg("Pair stats have a computation in flight")]
+    StatsBusy,
+    #[msg("Pair stats are no longer accepting fills")]
+    StatsClosed,
+    #[msg("Stats epoch has not ended")]
+    StatsEpochNotOver,
+    #[msg("Only verified single-shot fills are counted in pair stats")]
+    FillNotRecordable,
+    #[msg("Fill already counted in pair stats")]
+    StatsAlreadyRecorded,
+    #[msg("Invalid Disclosure PDA")]
+    InvalidDisclosurePDA,
+    #[msg("Auditor key must be a non-zero x25519 public key")]
+    InvalidAuditorKey,
+    #[msg("Order has not been admitted by its access policy")]
+    AccessNotGranted,
+    #[msg("An access check is already in flight")]
+    AccessCheckPending,
+    #[msg("Access policy has a computation in flight")]
+    PolicyBusy,
+    #[msg("Access policy has not been set")]
+    PolicyNotSet,
+    #[msg("Fill exceeds the escrowed deposit")]
+    FillExceedsDeposit,
+    #[msg("Fill has not passed verification")]
+    FillNotVerified,
+    #[msg("Fill did not fail verification")]
+    FillNotFailed,
+    #[msg("Unwind route must only sell the fill's output")]
+    InvalidUnwindRoute,
+    #[msg("Invalid pool policy PDA")]
+    InvalidPoolPolicy,
+    #[msg("Pool mints must be in ascending order")]
+    UnorderedPoolMints,
+    #[msg("Order still has a claim on the deposit")]
+    OrderStillLive,
+    #[msg("Maker inventory is locked by open quotes")]
+    MakerInventoryLocked,
+    #[msg("Quote has already been released")]
+    QuoteReleased,
+    #[msg("Losing quotes must be released before settlement")]
+    QuotesNotReleased,
+    #[msg("Batch computation has not timed out")]
+    BatchNotStuck,
+    #[msg("Order book computation has not timed out")]
+    BookNotStuck,
+    #[msg("Shielded balance computation has not timed out")]
+    BalanceNotStuck,
+    #[msg("Signer is not the program's upgrade authority")]
+    NotUpgradeAuthority,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
-pub struct SwapOrder {
+pub struct MatchResult {
+    pub bound: bool,
+    pub valid: bool,
     pub amount_in: u64,
-    pub amount_out_min: u64,
-    pub token_in: u64,
-    pub token_out: u64,
+    pub token_in: [u128; 2],
+    pub token_out: [u128; 2],
+    pub nonce: u64,
+    pub revision: u8,
+    pub deadline_slot: u64,
+    pub expired: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SharedEncryptedReceipt {
+    pub encryption_key: [u8; 32],
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; FILL_RECEIPT_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct MatchOrderOutput {
+    pub result: MatchResult,
+    pub receipt: SharedEncryptedReceipt,
+    pub iceberg: EncryptedIcebergState,
+    pub disclosure: SharedEncryptedOrder,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SharedEncryptedOrder {
+    pub encryption_key: [u8; 32],
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; SWAP_ORDER_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedIcebergState {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; ICEBERG_STATE_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct FillCheck {
+    pub passed: bool,
+    pub nonce: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CrossResult {
+    pub crossed: bool,
+    pub fill_a: u64,
+    pub fill_b: u64,
+    pub nonce_a: u64,
+    pub nonce_b: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedBatchBook {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; BATCH_BOOK_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BatchFill {
+    pub filled_in: u64,
+    pub amount_out: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BatchClearing {
+    pub price: u128,
+    pub fills: [BatchFill; BATCH_SIZE],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ExecutionOrder {
+    pub slots: [u8; BATCH_SIZE],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedOrderBook {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; BOOK_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BookInsertOutput {
+    pub book: EncryptedOrderBook,
+    pub accepted: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BookFill {
+    pub matched: bool,
+    pub slot: u8,
+    pub taker_gives: u64,
+    pub taker_receives: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BookMatchOutput {
+    pub book: EncryptedOrderBook,
+    pub fill: BookFill,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedRemainder {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; REMAINDER_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct RoundCheck {
+    pub passed: bool,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct FillRoundOutput {
+    pub remainder: EncryptedRemainder,
+    pub check: RoundCheck,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct TriggerCheck {
+    pub fire: bool,
     pub nonce: u64,
 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedTwapState {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; TWAP_STATE_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SliceRelease {
+    pub passed: bool,
+    pub amount: u64,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct NextSliceOutput {
+    pub state: EncryptedTwapState,
+    pub release: SliceRelease,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ClipRelease {
+    pub passed: bool,
+    pub clip: u64,
+    pub done: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct RefillClipOutput {
+    pub state: EncryptedIcebergState,
+    pub release: ClipRelease,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct RfqResult {
+    pub filled: bool,
+    pub winner: u8,
+    pub amount_out: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct OtcMatch {
+    pub compatible: bool,
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedBalance {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; BALANCE_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct DebitBalanceOutput {
+    pub balance: EncryptedBalance,
+    pub ok: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CrossShieldedOutput {
+    pub a_in: EncryptedBalance,
+    pub a_out: EncryptedBalance,
+    pub b_in: EncryptedBalance,
+    pub b_out: EncryptedBalance,
+    pub crossed: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PrivateTransferOutput {
+    pub from: EncryptedBalance,
+    pub to: EncryptedBalance,
+    pub ok: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedVolume {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; VOLUME_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ComputeFeeOutput {
+    pub volume: EncryptedVolume,
+    pub fee: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedPairStats {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; PAIR_STATS_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PairStatsBands {
+    pub base_volume_band: u8,
+    pub quote_volume_band: u8,
+    pub order_count_band: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct EncryptedAccessPolicy {
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; ACCESS_POLICY_CIPHERTEXTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AccessDecision {
+    pub allowed: bool,
+}
@@ -3,6 +3,16 @@ import { PublicKey } from "@solana/web3.js";
 import * as fs from "fs";
 import * as path from "path";
 import NodeWallet from "@coral-xyz/anchor/dist/cjs/nodewallet";
+import { randomBytes } from "crypto";
+import {
+  getArciumEnv,
+  getCompDefAccOffset,
+  getMXEAccAddress,
+  getMempoolAccAddress,
+  getCompDefAccAddress,
+  getExecutingPoolAccAddress,
+  getComputationAccAddress,
+} from "@arcium-hq/client";
 
 async function main() {
   const url =
@@ -54,18 +64,15 @@ async function main() {
       try {
         const nonce = new anchor.BN(event.nonce.toString());
         const amountIn = new anchor.BN(event.amount_in.toString());
-        const minOut = new anchor.BN(event.min_out.toString());
-        const tokenIn = new anchor.BN(event.token_in.toString());
-        const tokenOut = new anchor.BN(event.token_out.toString());
 
         console.log(`Processing order nonce: ${nonce.toString()}`);
 
         // Find TempWallet by nonce to get user and address
-        // Offset 120 = 8 (discriminator) + 32 (user) + 32 (token_in) + 32 (token_out) + 8 (amount_in) + 8 (min_out)
+        // Offset 112 = 8 (discriminator) + 32 (user) + 32 (token_in) + 32 (token_out) + 8 (amount_in)
         const tempWallets = await program.account.tempWallet.all([
           {
             memcmp: {
-              offset: 120,
+              offset: 112,
               bytes: nonce.toArrayLike(Buffer, "le", 8) as any, // Cast to any to avoid TS issues with Buffer
             },
           },
@@ -82,15 +89,6 @@ async function main() {
           program.programId
         )[0];
 
-        const tokenInMappingPda = PublicKey.findProgramAddressSync(
-          [Buffer.from("token"), tokenIn.toArrayLike(Buffer, "le", 8)],
-          program.programId
-        )[0];
-        const tokenOutMappingPda = PublicKey.findProgramAddressSync(
-          [Buffer.from("token"), tokenOut.toArrayLike(Buffer, "le", 8)],
-          program.programId
-        )[0];
-
         const settlementAcc = await program.account["settlementRequest"].fetch(
           settlementPda
         );
@@ -109,8 +107,6 @@ async function main() {
                 payer: provider.wallet.publicKey,
                 settlementRequest: settlementPda,
                 tempWallet: tempWalletPubkey,
-                tokenInMapping: tokenInMappingPda,
-                tokenOutMapping: tokenOutMappingPda,
                 jupiterProgram: jupiterProgramId,
               })
               .rpc();
@@ -128,9 +124,7 @@ async function main() {
                 payer: provider.wallet.publicKey,
                 settlementRequest: settlementPda,
                 tempWallet: tempWalletPubkey,
-                tokenInMapping: tokenInMappingPda,
-                tokenOutMapping: tokenOutMappingPda,
-                jupiterProgram: anchor.web3.SystemProgram.programId,
+                jupiterProgram: jupiterProgramId,
               })
               .rpc();
             console.log("Test Swap executed");
@@ -144,7 +138,104 @@ async function main() {
     }
   );
 
+  // Limit orders sit armed until check_trigger sees the oracle cross their
+  // hidden price; its callback then emits OrderSettledEvent as above.
+  const triggerIntervalMs = Number(process.env.TRIGGER_INTERVAL_MS || 10_000);
+  const crankTriggers = async () => {
+    const settlements = await program.account.settlementRequest.all();
+    for (const { publicKey, account } of settlements) {
+      if (!account.awaitingTrigger) continue;
+      try {
+        const nonce = new anchor.BN(account.nonce.toString());
+        const tempWallets = await program.account.tempWallet.all([
+          {
+            memcmp: {
+              offset: 112,
+              bytes: nonce.toArrayLike(Buffer, "le", 8) as any,
+            },
+          },
+        ]);
+        if (tempWallets.length === 0) continue;
+        const tempWallet = tempWallets[0];
+
+        const computationOffset = new anchor.BN(randomBytes(8), "hex");
+        await program.methods
+          .checkTrigger(computationOffset)
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            settlementRequest: publicKey,
+            tempWallet: tempWallet.publicKey,
+            priceFeed: tempWallet.account.priceFeed,
+            computationAccount: getComputationAccAddress(
+              program.programId,
+              computationOffset
+            ),
+            clusterAccount: getArciumEnv().arciumClusterPubkey,
+            mxeAccount: getMXEAccAddress(program.programId),
+            mempoolAccount: getMempoolAccAddress(program.programId),
+            executingPool: getExecutingPoolAccAddress(program.programId),
+            compDefAccount: getCompDefAccAddress(
+              program.programId,
+              Buffer.from(getCompDefAccOffset("check_trigger")).readUInt32LE()
+            ),
+          })
+          .rpc({ skipPreflight: true, commitment: "confirmed" });
+        console.log(`Queued trigger check for nonce ${nonce.toString()}`);
+      } catch (e) {
+        console.error("Trigger check failed", e);
+      }
+    }
+  };
+  // TWAP schedules release their next slice once the cadence allows; the
+  // callback emits OrderSettledEvent with the slice amount.
+  const crankTwaps = async () => {
+    const schedules = await program.account.twapSchedule.all();
+    const currentSlot = await connection.getSlot("confirmed");
+    for (const { account } of schedules) {
+      if (account.finished || account.pending) continue;
+      if (currentSlot < Number(account.nextSlot.toString())) continue;
+      try {
+        const nonce = new anchor.BN(account.nonce.toString());
+        const settlementPda = PublicKey.findProgramAddressSync(
+          [Buffer.from("settlement"), nonce.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+
+        const computationOffset = new anchor.BN(randomBytes(8), "hex");
+        await program.methods
+          .releaseTwapSlice(computationOffset)
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            settlementRequest: settlementPda,
+            tempWallet: account.tempWallet,
+            computationAccount: getComputationAccAddress(
+              program.programId,
+              computationOffset
+            ),
+            clusterAccount: getArciumEnv().arciumClusterPubkey,
+            mxeAccount: getMXEAccAddress(program.programId),
+            mempoolAccount: getMempoolAccAddress(program.programId),
+            executingPool: getExecutingPoolAccAddress(program.programId),
+            compDefAccount: getCompDefAccAddress(
+              program.programId,
+              Buffer.from(getCompDefAccOffset("next_slice")).readUInt32LE()
+            ),
+          })
+          .rpc({ skipPreflight: true, commitment: "confirmed" });
+        console.log(`Queued TWAP slice for nonce ${nonce.toString()}`);
+      } catch (e) {
+        console.error("TWAP slice release failed", e);
+      }
+    }
+  };
+
+  const triggerTimer = setInterval(() => {
+    crankTriggers().catch((e) => console.error("Trigger crank error", e));
+    crankTwaps().catch((e) => console.error("TWAP crank error", e));
+  }, triggerIntervalMs);
+
   process.on("SIGINT", async () => {
+    clearInterval(triggerTimer);
     try {
       await program.removeEventListener(listener);
     } catch {}
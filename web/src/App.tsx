@@ -7,7 +7,7 @@ import { WalletAdapterNetwork } from "@solana/wallet-adapter-base";
 import { useAnchorWallet, useWallet } from "@solana/wallet-adapter-react";
 import { AnchorProvider, BN } from "@coral-xyz/anchor";
 import { PublicKey, Transaction, TransactionInstruction } from "@solana/web3.js";
-import { getProvider, getProgram, PROGRAM_ID, findSettlementPda, findTokenMapPda, findTempWalletPda } from "./anchor";
+import { getProvider, getProgram, PROGRAM_ID, findSettlementPda, findTempWalletPda } from "./anchor";
 import { getNetwork } from "./utils/network";
 import { getSwapQuote } from "./services/quotes";
 import "./styles.css";
@@ -86,37 +86,6 @@ function Inner() {
     return () => clearTimeout(handler);
   }, [amount, fromToken, toToken, program]);
 
-  useEffect(() => {
-    if (!program || !wallet?.publicKey) return;
-    (async () => {
-      try {
-        for (const [symbol, info] of Object.entries(TOKEN_REGISTRY)) {
-          const id = new BN(info.tokenId);
-          const pda = findTokenMapPda(program.programId, id);
-          try {
-            // Check if already registered
-            await program.account.tokenMapping.fetch(pda);
-            console.log(`Token ${symbol} already registered`);
-          } catch (e) {
-            // Not registered, try to register
-            console.log(`Registering token ${symbol}...`);
-            try {
-              await program.methods
-                .registerToken(id)
-                .accounts({ payer: wallet.publicKey, tokenMapping: pda, mint: info.mint })
-                .rpc();
-              console.log(`Registered ${symbol}`);
-            } catch (regError) {
-              console.warn(`Failed to register ${symbol}:`, regError);
-            }
-          }
-        }
-      } catch (e) {
-        // silent
-      }
-    })();
-  }, [program, wallet?.publicKey]);
-
   const fetchBalance = async (tokenSymbol: string) => {
     if (!program || !wallet?.publicKey) return;
     try {
@@ -317,16 +286,12 @@ function Inner() {
       // But for "Option 1" style, let's keep it here but clearly mark it as part of the test flow setup
       if (network !== 'mainnet') {
          console.log("Simulating Match Order for Test Network...");
-         const tokenInId = new BN(fromInfo.tokenId);
-         const tokenOutId = new BN(toInfo.tokenId);
-         
          const simTx = new Transaction();
          const simIx = await program.methods
             .simulateMatchOrder(
                 amtIn,
-                minOut,
-                tokenInId,
-                tokenOutId,
+                fromInfo.mint,
+                toInfo.mint,
                 n
             )
             .accounts({
@@ -391,13 +356,6 @@ function Inner() {
                 const network = getNetwork(program.provider.connection);
                 if (network !== 'mainnet' && tempWallet.isFunded && acc.active && step === "processing") {
                     console.log("Auto-executing swap for test network...");
-                    // We need token IDs and empty route data
-                    const fromInfo = TOKEN_REGISTRY[fromToken];
-                    const toInfo = TOKEN_REGISTRY[toToken];
-                    // Find mapping PDAs
-                    const tokenInMappingPda = findTokenMapPda(program.programId, new BN(fromInfo.tokenId));
-                    const tokenOutMappingPda = findTokenMapPda(program.programId, new BN(toInfo.tokenId));
-                    
                     try {
                         await program.methods
                             .executeSwapTest(n)
@@ -405,9 +363,7 @@ function Inner() {
                                 payer: wallet.publicKey,
                                 settlementRequest: settlementPda,
                                 tempWallet: new PublicKey(tempWalletAddr),
-                                tokenInMapping: tokenInMappingPda,
-                                tokenOutMapping: tokenOutMappingPda,
-                                jupiterProgram: new PublicKey("11111111111111111111111111111111"), // Dummy
+                                jupiterProgram: new PublicKey("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4"), // Pinned by the program, unused in test mode
                             })
                             .rpc();
                          console.log("Execute Swap Test sent");
@@ -734,22 +690,19 @@ export default function App() {
 
 const TOKEN_REGISTRY: Record<
   string,
-  { tokenId: number; mint: PublicKey; decimals: number; icon: string }
+  { mint: PublicKey; decimals: number; icon: string }
 > = {
   USDC: {
-    tokenId: 1,
     mint: new PublicKey("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU"),
     decimals: 6,
     icon: "💵",
   },
   SOL: {
-    tokenId: 2,
     mint: new PublicKey("So11111111111111111111111111111111111111112"),
     decimals: 9,
     icon: "◎",
   },
   USDT: {
-    tokenId: 3,
     mint: new PublicKey("EJwZgeZrdC8TXTQbQBoL6bfuAnFUUy1PVCMB4DYPzVaS"),
     decimals: 6,
     icon: "💲",
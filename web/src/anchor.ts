@@ -72,13 +72,6 @@ export function findSettlementPda(programId: PublicKey, nonce: BN) {
   )[0];
 }
 
-export function findTokenMapPda(programId: PublicKey, id: BN) {
-  return PublicKey.findProgramAddressSync(
-    [new TextEncoder().encode("token"), new Uint8Array(id.toArray("le", 8))],
-    programId
-  )[0];
-}
-
 export function findTempWalletPda(
   programId: PublicKey,
   user: PublicKey,